@@ -1,5 +1,7 @@
 //! Simple badge generator
 
+use std::{error::Error, fmt, str::FromStr};
+
 use base64::display::Base64Display;
 use once_cell::sync::Lazy;
 use rusttype::{point, Font, Point, PositionedGlyph, Scale};
@@ -11,6 +13,45 @@ const SCALE: Scale = Scale {
     y: FONT_SIZE,
 };
 
+/// Visual style of a badge, named like the shields.io styles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BadgeStyle {
+    /// Rounded corners with a subtle gradient
+    #[default]
+    Flat,
+    /// Square corners without a gradient
+    FlatSquare,
+    /// Rounded corners with a glossy gradient
+    Plastic,
+    /// Taller and square, with uppercase bold text
+    ForTheBadge,
+}
+
+impl FromStr for BadgeStyle {
+    type Err = ParseStyleError;
+
+    fn from_str(s: &str) -> Result<BadgeStyle, ParseStyleError> {
+        match s {
+            "flat" => Ok(BadgeStyle::Flat),
+            "flat-square" => Ok(BadgeStyle::FlatSquare),
+            "plastic" => Ok(BadgeStyle::Plastic),
+            "for-the-badge" => Ok(BadgeStyle::ForTheBadge),
+            _ => Err(ParseStyleError(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseStyleError(String);
+
+impl fmt::Display for ParseStyleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown badge style {:?}", self.0)
+    }
+}
+
+impl Error for ParseStyleError {}
+
 pub struct BadgeOptions {
     /// Subject will be displayed on the left side of badge
     pub subject: String,
@@ -18,6 +59,9 @@ pub struct BadgeOptions {
     pub status: String,
     /// HTML color of badge
     pub color: String,
+    pub style: BadgeStyle,
+    /// URL of an image shown in front of the subject, usually a data URI
+    pub logo: Option<String>,
 }
 
 impl Default for BadgeOptions {
@@ -26,10 +70,94 @@ impl Default for BadgeOptions {
             subject: "build".to_owned(),
             status: "passing".to_owned(),
             color: "#4c1".to_owned(),
+            style: BadgeStyle::default(),
+            logo: None,
+        }
+    }
+}
+
+/// Dimensions and decorations that make up a style
+struct Layout {
+    height: u32,
+    radius: u32,
+    /// Stops of the gradient laid over the badge, as `(offset, color, opacity)`
+    gradient: &'static [(f32, &'static str, f32)],
+    /// Horizontal padding on each side of a text
+    padding: u32,
+    font_size: u32,
+    /// Baseline of the texts, whose shadow is drawn one pixel lower if enabled
+    text_y: u32,
+    shadow: bool,
+    for_the_badge: bool,
+}
+
+impl BadgeStyle {
+    fn layout(self) -> Layout {
+        match self {
+            BadgeStyle::Flat => Layout {
+                height: 20,
+                radius: 3,
+                gradient: &[(0., "#bbb", 0.1), (1., "#000", 0.1)],
+                padding: 3,
+                font_size: 11,
+                text_y: 14,
+                shadow: true,
+                for_the_badge: false,
+            },
+            BadgeStyle::FlatSquare => Layout {
+                height: 20,
+                radius: 0,
+                gradient: &[],
+                padding: 3,
+                font_size: 11,
+                text_y: 14,
+                shadow: false,
+                for_the_badge: false,
+            },
+            BadgeStyle::Plastic => Layout {
+                height: 18,
+                radius: 4,
+                gradient: &[
+                    (0., "#fff", 0.7),
+                    (0.1, "#aaa", 0.1),
+                    (0.9, "#000", 0.3),
+                    (1., "#000", 0.5),
+                ],
+                padding: 3,
+                font_size: 11,
+                text_y: 13,
+                shadow: true,
+                for_the_badge: false,
+            },
+            BadgeStyle::ForTheBadge => Layout {
+                height: 28,
+                radius: 0,
+                gradient: &[],
+                padding: 9,
+                font_size: 10,
+                text_y: 18,
+                shadow: false,
+                for_the_badge: true,
+            },
         }
     }
 }
 
+/// Texts of a badge, and the widths of its parts
+struct Geometry {
+    layout: Layout,
+    subject: String,
+    status: String,
+    /// Space taken by the logo at the start of the left part
+    logo_width: u32,
+    left_width: u32,
+    right_width: u32,
+}
+
+/// Width of a logo, and the space between it and the subject
+const LOGO_WIDTH: u32 = 14;
+const LOGO_SPACING: u32 = 3;
+
 struct BadgeStaticData {
     font: Font<'static>,
     scale: Scale,
@@ -66,53 +194,182 @@ impl Badge {
     }
 
     pub fn to_svg(&self) -> String {
-        let left_width = self.calculate_width(&self.options.subject) + 6;
-        let right_width = self.calculate_width(&self.options.status) + 6;
-
-        let svg = format!(
-            r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="20">
-  <linearGradient id="smooth" x2="0" y2="100%">
-    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
-    <stop offset="1" stop-opacity=".1"/>
-  </linearGradient>
-
-  <mask id="round">
-    <rect width="{}" height="20" rx="3" fill="#fff"/>
-  </mask>
-
-  <g mask="url(#round)">
-    <rect width="{}" height="20" fill="#555"/>
-    <rect x="{}" width="{}" height="20" fill="{}"/>
-    <rect width="{}" height="20" fill="url(#smooth)"/>
-  </g>
-
-  <g fill="#fff" text-anchor="middle" font-family="DejaVu Sans,Verdana,Geneva,sans-serif" font-size="11">
-    <text x="{}" y="15" fill="#010101" fill-opacity=".3">{}</text>
-    <text x="{}" y="14">{}</text>
-    <text x="{}" y="15" fill="#010101" fill-opacity=".3">{}</text>
-    <text x="{}" y="14">{}</text>
-  </g>
-</svg>"###,
-            left_width + right_width,
-            left_width + right_width,
-            left_width,
+        let Geometry {
+            layout,
+            subject,
+            status,
+            logo_width,
             left_width,
             right_width,
-            self.options.color,
-            left_width + right_width,
-            (left_width) / 2,
-            self.options.subject,
-            (left_width) / 2,
-            self.options.subject,
-            left_width + (right_width / 2),
-            self.options.status,
-            left_width + (right_width / 2),
-            self.options.status
+        } = self.geometry(self.options.logo.is_some());
+        let width = left_width + right_width;
+        let height = layout.height;
+
+        let mut svg = format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}">
+"##,
+            width, height
         );
 
+        if !layout.gradient.is_empty() {
+            svg.push_str("  <linearGradient id=\"smooth\" x2=\"0\" y2=\"100%\">\n");
+            for (offset, color, opacity) in layout.gradient {
+                svg.push_str(&format!(
+                    "    <stop offset=\"{}\" stop-color=\"{}\" stop-opacity=\"{}\"/>\n",
+                    offset, color, opacity
+                ));
+            }
+            svg.push_str("  </linearGradient>\n\n");
+        }
+
+        svg.push_str(&format!(
+            r##"  <clipPath id="round">
+    <rect width="{width}" height="{height}" rx="{radius}" fill="#fff"/>
+  </clipPath>
+
+  <g clip-path="url(#round)">
+    <rect width="{left}" height="{height}" fill="#555"/>
+    <rect x="{left}" width="{right}" height="{height}" fill="{color}"/>
+"##,
+            width = width,
+            height = height,
+            radius = layout.radius,
+            left = left_width,
+            right = right_width,
+            color = escape(&self.options.color),
+        ));
+        if !layout.gradient.is_empty() {
+            svg.push_str(&format!(
+                "    <rect width=\"{}\" height=\"{}\" fill=\"url(#smooth)\"/>\n",
+                width, height
+            ));
+        }
+        svg.push_str("  </g>\n\n");
+
+        if let Some(ref logo) = self.options.logo {
+            svg.push_str(&format!(
+                "  <image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" xlink:href=\"{}\"/>\n\n",
+                layout.padding + 2,
+                (height - LOGO_WIDTH) / 2,
+                LOGO_WIDTH,
+                LOGO_WIDTH,
+                escape(logo)
+            ));
+        }
+
+        let font_attrs = if layout.for_the_badge {
+            r#" font-weight="bold" letter-spacing="1""#
+        } else {
+            ""
+        };
+        svg.push_str(&format!(
+            "  <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"DejaVu Sans,Verdana,Geneva,sans-serif\" font-size=\"{}\"{}>\n",
+            layout.font_size, font_attrs
+        ));
+        let texts = [
+            (logo_width + (left_width - logo_width) / 2, &subject),
+            (left_width + right_width / 2, &status),
+        ];
+        for (x, text) in &texts {
+            if layout.shadow {
+                svg.push_str(&format!(
+                    "    <text x=\"{}\" y=\"{}\" fill=\"#010101\" fill-opacity=\".3\">{}</text>\n",
+                    x,
+                    layout.text_y + 1,
+                    escape(text)
+                ));
+            }
+            svg.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\">{}</text>\n",
+                x,
+                layout.text_y,
+                escape(text)
+            ));
+        }
+        svg.push_str("  </g>\n</svg>");
+
         svg
     }
 
+    /// Renders the badge as a PNG image. Logos are left out, as they are usually SVG images.
+    pub fn to_png(&self) -> Vec<u8> {
+        let geometry = self.geometry(false);
+        let layout = &geometry.layout;
+        let width = geometry.left_width + geometry.right_width;
+        let height = layout.height;
+
+        let mut canvas = Canvas::new(width, height);
+        let right_color = parse_color(&self.options.color).unwrap_or([0x9f, 0x9f, 0x9f]);
+        canvas.fill_rounded(
+            geometry.left_width,
+            layout.radius,
+            [0x55, 0x55, 0x55],
+            right_color,
+        );
+        if !layout.gradient.is_empty() {
+            canvas.overlay_gradient(layout.gradient);
+        }
+
+        let scale = Scale::uniform(layout.font_size as f32);
+        let spacing = if layout.for_the_badge { 1. } else { 0. };
+        let texts = [
+            (geometry.left_width / 2, &geometry.subject),
+            (
+                geometry.left_width + geometry.right_width / 2,
+                &geometry.status,
+            ),
+        ];
+        for (x, text) in &texts {
+            if layout.shadow {
+                let baseline = point(*x as f32, (layout.text_y + 1) as f32);
+                canvas.draw_text(text, scale, spacing, baseline, [0x01, 0x01, 0x01], 0.3);
+            }
+            let baseline = point(*x as f32, layout.text_y as f32);
+            canvas.draw_text(text, scale, spacing, baseline, [0xff, 0xff, 0xff], 1.);
+        }
+
+        canvas.encode_png()
+    }
+
+    /// Lays out the texts of the badge in its style
+    fn geometry(&self, with_logo: bool) -> Geometry {
+        let layout = self.options.style.layout();
+        let (subject, status) = if layout.for_the_badge {
+            (
+                self.options.subject.to_uppercase(),
+                self.options.status.to_uppercase(),
+            )
+        } else {
+            (self.options.subject.clone(), self.options.status.clone())
+        };
+
+        let text_width = |text: &str| {
+            let width = self.calculate_width(text);
+            if layout.for_the_badge {
+                // bold text with letter spacing is wider than it is measured
+                width + width / 10 + text.chars().count() as u32
+            } else {
+                width
+            }
+        };
+        let logo_width = if with_logo {
+            LOGO_WIDTH + LOGO_SPACING
+        } else {
+            0
+        };
+        let left_width = logo_width + text_width(&subject) + 2 * layout.padding;
+        let right_width = text_width(&status) + 2 * layout.padding;
+
+        Geometry {
+            layout,
+            subject,
+            status,
+            logo_width,
+            left_width,
+            right_width,
+        }
+    }
+
     fn calculate_width(&self, text: &str) -> u32 {
         let glyphs: Vec<PositionedGlyph> =
             DATA.font.layout(text, DATA.scale, DATA.offset).collect();
@@ -129,6 +386,189 @@ impl Badge {
     }
 }
 
+/// RGBA pixels, where the alpha channel is the coverage of the rounded badge shape
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    /// Fills the canvas with a rounded rectangle whose left part has a different color
+    fn fill_rounded(&mut self, split: u32, radius: u32, left: [u8; 3], right: [u8; 3]) {
+        let radius = radius as f32;
+        let (width, height) = (self.width as f32, self.height as f32);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                // distance from the center of the nearest corner's arc, if in a corner
+                let dx = (radius - cx).max(cx - (width - radius)).max(0.);
+                let dy = (radius - cy).max(cy - (height - radius)).max(0.);
+                let coverage = if radius > 0. && dx > 0. && dy > 0. {
+                    (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0., 1.)
+                } else {
+                    1.
+                };
+
+                let [r, g, b] = if x < split { left } else { right };
+                let i = ((y * self.width + x) * 4) as usize;
+                self.pixels[i..i + 4].copy_from_slice(&[r, g, b, (coverage * 255.).round() as u8]);
+            }
+        }
+    }
+
+    fn overlay_gradient(&mut self, stops: &[(f32, &str, f32)]) {
+        for y in 0..self.height {
+            let offset = (y as f32 + 0.5) / self.height as f32;
+            let (color, opacity) = gradient_at(stops, offset);
+
+            for x in 0..self.width {
+                self.blend(x, y, color, opacity);
+            }
+        }
+    }
+
+    /// Draws text centered around the given point on its baseline
+    fn draw_text(
+        &mut self,
+        text: &str,
+        scale: Scale,
+        spacing: f32,
+        center: Point<f32>,
+        color: [u8; 3],
+        opacity: f32,
+    ) {
+        let color = [color[0] as f32, color[1] as f32, color[2] as f32];
+        let glyphs: Vec<_> = DATA
+            .font
+            .layout(text, scale, point(0., 0.))
+            .enumerate()
+            .map(|(i, glyph)| {
+                let position = glyph.position();
+                glyph
+                    .into_unpositioned()
+                    .positioned(point(position.x + i as f32 * spacing, position.y))
+            })
+            .collect();
+        let width = glyphs
+            .last()
+            .map(|glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.);
+
+        let origin = point((center.x - width / 2.).round(), center.y);
+        for glyph in glyphs {
+            let position = glyph.position();
+            let glyph = glyph
+                .into_unpositioned()
+                .positioned(point(origin.x + position.x, origin.y + position.y));
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+
+            glyph.draw(|gx, gy, value| {
+                let x = bounds.min.x + gx as i32;
+                let y = bounds.min.y + gy as i32;
+                if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+                    self.blend(x as u32, y as u32, color, value * opacity);
+                }
+            });
+        }
+    }
+
+    /// Blends a color over the color channels of a pixel, keeping its alpha channel
+    fn blend(&mut self, x: u32, y: u32, color: [f32; 3], opacity: f32) {
+        let i = ((y * self.width + x) * 4) as usize;
+        for (channel, value) in self.pixels[i..i + 3].iter_mut().zip(&color) {
+            let blended = *channel as f32 * (1. - opacity) + value * opacity;
+            *channel = blended.round().clamp(0., 255.) as u8;
+        }
+    }
+
+    fn encode_png(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder
+                .write_header()
+                .expect("writing to a Vec cannot fail");
+            writer
+                .write_image_data(&self.pixels)
+                .expect("image data matches the dimensions");
+        }
+        png
+    }
+}
+
+/// Interpolates the color and opacity of a gradient at the given offset
+fn gradient_at(stops: &[(f32, &str, f32)], offset: f32) -> ([f32; 3], f32) {
+    let rgb = |color: &str| {
+        let [r, g, b] = parse_color(color).unwrap_or([0, 0, 0]);
+        [r as f32, g as f32, b as f32]
+    };
+
+    let next = stops
+        .iter()
+        .position(|&(stop, _, _)| stop >= offset)
+        .unwrap_or(stops.len() - 1);
+    if next == 0 {
+        let (_, color, opacity) = stops[0];
+        return (rgb(color), opacity);
+    }
+
+    let (from, from_color, from_opacity) = stops[next - 1];
+    let (to, to_color, to_opacity) = stops[next];
+    let t = (offset - from) / (to - from);
+    let (from_color, to_color) = (rgb(from_color), rgb(to_color));
+    let mut color = [0.; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel = from_color[i] + (to_color[i] - from_color[i]) * t;
+    }
+    (color, from_opacity + (to_opacity - from_opacity) * t)
+}
+
+/// Parses colors in the `#rgb` and `#rrggbb` notations
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+
+    match *digits.as_slice() {
+        [r, g, b] => Some([r * 17, g * 17, b * 17]),
+        [r1, r2, g1, g2, b1, b2] => Some([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2]),
+        _ => None,
+    }
+}
+
+/// Escapes text for use in SVG content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,6 +584,70 @@ mod tests {
         assert_eq!(badge.calculate_width("passing"), 44);
     }
 
+    #[test]
+    fn test_styles() {
+        let flat = Badge::new(options()).to_svg();
+        assert!(flat.contains(r#"height="20""#));
+        assert!(flat.contains("url(#smooth)"));
+
+        let flat_square = Badge::new(BadgeOptions {
+            style: "flat-square".parse().unwrap(),
+            ..options()
+        })
+        .to_svg();
+        assert!(!flat_square.contains("linearGradient"));
+        assert!(flat_square.contains(r#"rx="0""#));
+
+        let for_the_badge = Badge::new(BadgeOptions {
+            style: BadgeStyle::ForTheBadge,
+            ..options()
+        })
+        .to_svg();
+        assert!(for_the_badge.contains(r#"height="28""#));
+        assert!(for_the_badge.contains(">PASSING</text>"));
+
+        assert!("round".parse::<BadgeStyle>().is_err());
+    }
+
+    #[test]
+    fn test_escapes_text_and_logo() {
+        let badge = Badge::new(BadgeOptions {
+            subject: "<script>".to_owned(),
+            logo: Some("data:image/svg+xml;base64,\"/>".to_owned()),
+            ..options()
+        });
+        let svg = badge.to_svg();
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(svg.contains(r#"xlink:href="data:image/svg+xml;base64,&quot;/&gt;""#));
+    }
+
+    #[test]
+    fn test_to_png() {
+        let png = Badge::new(options()).to_png();
+        let decoder = png::Decoder::new(png.as_slice());
+        let (info, mut reader) = decoder.read_info().unwrap();
+        assert_eq!(info.color_type, png::ColorType::RGBA);
+        assert_eq!(info.height, 20);
+
+        let svg = Badge::new(options()).to_svg();
+        assert!(svg.contains(&format!(r#"width="{}""#, info.width)));
+
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        // the corners are rounded, but the middle of the badge is opaque
+        assert_eq!(pixels[3], 0);
+        let middle = ((10 * info.width + info.width / 2) * 4 + 3) as usize;
+        assert_eq!(pixels[middle], 255);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#4c1"), Some([0x44, 0xcc, 0x11]));
+        assert_eq!(parse_color("#e05d44"), Some([0xe0, 0x5d, 0x44]));
+        assert_eq!(parse_color("red"), None);
+        assert_eq!(parse_color("#12345"), None);
+    }
+
     #[test]
     #[ignore]
     fn test_to_svg() {
@@ -0,0 +1,292 @@
+//! Analyzes the dependencies of a local checkout against the local crates index, so that the
+//! judgment of the status badge can run as a CI step.
+
+#![deny(rust_2018_idioms)]
+
+use std::{
+    collections::HashMap, io, path::PathBuf, process, str::FromStr, sync::Mutex, time::Duration,
+};
+
+use anyhow::{bail, Error};
+use slog::{o, Drain, Level, Logger};
+use structopt::StructOpt;
+
+use shiny_robots::config::Config;
+use shiny_robots::engine::{AnalyzeDependenciesOutcome, Engine};
+use shiny_robots::models::crates::{AnalyzedDependency, RegistryName};
+use shiny_robots::server::views;
+use shiny_robots::utils::advisory_db::ManagedAdvisoryDb;
+use shiny_robots::utils::history::History;
+use shiny_robots::utils::index::ManagedIndex;
+use shiny_robots::DEPS_RS_UA;
+
+/// Exit code for checkouts that fail the check
+const EXIT_FAILED: i32 = 1;
+/// Exit code for checkouts that could not be analyzed
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug)]
+enum Format {
+    Table,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            _ => bail!("unknown format {:?}", s),
+        }
+    }
+}
+
+/// Checks whether the dependencies of a local checkout are up to date and secure
+#[derive(Debug, StructOpt)]
+#[structopt(name = "deps-rs")]
+struct Opt {
+    /// Directory of the root `Cargo.toml`
+    #[structopt(default_value = ".", parse(from_os_str))]
+    path: PathBuf,
+    /// Output format
+    #[structopt(long, default_value = "table", possible_values = &["table", "json"])]
+    format: Format,
+    /// Number of outdated dependencies that still passes the check
+    #[structopt(long, default_value = "0")]
+    max_outdated: usize,
+}
+
+fn init_logger() -> Logger {
+    let decorator = slog_term::TermDecorator::new().stderr().build();
+    let drain = slog_term::CompactFormat::new(decorator).build();
+    // logged synchronously, so that nothing is lost when exiting early
+    let drain = Mutex::new(drain).filter_level(Level::Warning).fuse();
+
+    Logger::root(drain, o!())
+}
+
+/// Sets up an engine from the local crates index and advisory database, which are only
+/// downloaded if they don't exist yet
+async fn init_engine(logger: &Logger) -> Result<Engine, Error> {
+    let config = Config::load()?;
+
+    let client = reqwest::Client::builder()
+        .user_agent(DEPS_RS_UA)
+        .timeout(Duration::from_secs(5))
+        .build()?;
+
+    let mut managed_index = ManagedIndex::new(Duration::from_secs(20), logger.clone());
+    managed_index.initial_clone().await?;
+
+    let mut registries = HashMap::new();
+    for registry in &config.registries {
+        let name: RegistryName = registry.name.parse()?;
+        let mut managed_index = ManagedIndex::for_registry(
+            &registry.name,
+            &registry.index,
+            Duration::from_secs(60),
+            logger.clone(),
+        )?;
+        managed_index.initial_clone().await?;
+        registries.insert(name, managed_index.index());
+    }
+
+    let mut managed_advisory_db = ManagedAdvisoryDb::new(Duration::from_secs(1800), logger.clone());
+    managed_advisory_db.initial_clone().await?;
+
+    Ok(Engine::new(
+        client,
+        managed_index.index(),
+        registries,
+        managed_advisory_db.db(),
+        config.site_tokens(),
+        History::in_memory()?,
+        logger.clone(),
+    ))
+}
+
+fn dependency_status(dep: &AnalyzedDependency) -> String {
+    if dep.is_insecure() {
+        let ids: Vec<_> = dep
+            .vulnerabilities
+            .iter()
+            .map(|advisory| advisory.id().as_str())
+            .collect();
+        format!("insecure ({})", ids.join(", "))
+    } else if dep.is_outdated() {
+        "outdated".to_string()
+    } else if dep.pinned && dep.is_behind() {
+        "pinned".to_string()
+    } else {
+        "up to date".to_string()
+    }
+}
+
+fn print_table(outcome: &AnalyzeDependenciesOutcome) {
+    let mut rows = vec![[
+        "Crate".to_string(),
+        "Kind".to_string(),
+        "Dependency".to_string(),
+        "Required".to_string(),
+        "Latest".to_string(),
+        "Status".to_string(),
+    ]];
+
+    for (crate_name, deps) in &outcome.crates {
+        let kinds = [
+            ("normal", &deps.main),
+            ("dev", &deps.dev),
+            ("build", &deps.build),
+        ];
+        for (kind, deps) in &kinds {
+            let declarations = deps
+                .iter()
+                .flat_map(|(name, dep)| dep.declarations().map(move |dep| (name, dep)));
+            for (name, dep) in declarations {
+                rows.push([
+                    crate_name.as_ref().to_string(),
+                    kind.to_string(),
+                    name.as_ref().to_string(),
+                    dep.required.to_string(),
+                    dep.latest
+                        .as_ref()
+                        .map_or_else(|| "-".to_string(), ToString::to_string),
+                    dependency_status(dep),
+                ]);
+            }
+        }
+        for name in &deps.ignored {
+            rows.push([
+                crate_name.as_ref().to_string(),
+                "-".to_string(),
+                name.as_ref().to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                "ignored".to_string(),
+            ]);
+        }
+    }
+
+    if let Some(ref lockfile) = outcome.lockfile {
+        for pkg in lockfile.packages.iter().filter(|pkg| pkg.is_insecure()) {
+            let ids: Vec<_> = pkg
+                .vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().as_str())
+                .collect();
+            rows.push([
+                "Cargo.lock".to_string(),
+                "locked".to_string(),
+                pkg.name.as_ref().to_string(),
+                format!("={}", pkg.version),
+                "-".to_string(),
+                format!("insecure ({})", ids.join(", ")),
+            ]);
+        }
+        for name in &lockfile.ignored {
+            rows.push([
+                "Cargo.lock".to_string(),
+                "locked".to_string(),
+                name.as_ref().to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                "ignored".to_string(),
+            ]);
+        }
+    }
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in &rows {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+
+    println!();
+    println!("dependencies: {}", summary(outcome));
+}
+
+/// Describes the outcome the way the status badge does
+fn summary(outcome: &AnalyzeDependenciesOutcome) -> String {
+    let (outdated, total) = outcome.outdated_ratio();
+
+    if outcome.any_insecure() {
+        "insecure".to_string()
+    } else if outdated > 0 {
+        format!("{} of {} outdated", outdated, total)
+    } else if total > 0 {
+        "up to date".to_string()
+    } else {
+        "none".to_string()
+    }
+}
+
+fn passes(outcome: &AnalyzeDependenciesOutcome, max_outdated: usize) -> bool {
+    let (outdated, _) = outcome.outdated_ratio();
+    !outcome.any_insecure() && outdated <= max_outdated
+}
+
+#[tokio::main]
+async fn main() {
+    let opt = Opt::from_args();
+    let logger = init_logger();
+
+    let engine = match init_engine(&logger).await {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!(
+                "failed to load the crates index or advisory database: {:#}",
+                err
+            );
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    let outcome = match engine.analyze_local_dependencies(opt.path.clone()).await {
+        Ok(outcome) => outcome,
+        Err(failure) => {
+            eprintln!("failed to analyze {}: {}", opt.path.display(), failure);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    match opt.format {
+        Format::Table => print_table(&outcome),
+        Format::Json => {
+            let stdout = io::stdout();
+            serde_json::to_writer_pretty(stdout.lock(), &views::json::outcome(&outcome))
+                .expect("failed to write analysis");
+            println!();
+        }
+    }
+
+    if !passes(&outcome, opt.max_outdated) {
+        process::exit(EXIT_FAILED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_arguments() {
+        let opt = Opt::from_iter(&["deps-rs", "--format", "json", "--max-outdated", "3", "repo"]);
+        assert!(matches!(opt.format, Format::Json));
+        assert_eq!(opt.max_outdated, 3);
+        assert_eq!(opt.path, PathBuf::from("repo"));
+
+        assert!(Opt::from_iter_safe(&["deps-rs", "--format", "xml"]).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashMap, env, fmt, fs, path::PathBuf, sync::Arc};
+
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+
+use crate::models::repo::{CustomSite, RepoSite};
+
+/// Optional service configuration, read from the TOML file named by `DEPS_RS_CONFIG`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Self-hosted forges served under `/repo/:name/...`
+    #[serde(default)]
+    pub sites: Vec<CustomSite>,
+    /// Access tokens for private repositories, keyed by site name
+    #[serde(default)]
+    pub tokens: HashMap<String, AccessToken>,
+    /// Alternate registries, selected through the `registry` key of dependencies
+    #[serde(default)]
+    pub registries: Vec<RegistryConfig>,
+    /// SQLite database keeping the analysis history, which is held in memory if unset
+    pub history_db: Option<PathBuf>,
+    /// Secrets of push webhooks, keyed by site name
+    #[serde(default)]
+    pub webhook_secrets: HashMap<String, AccessToken>,
+    /// Mail server used to send notifications to email addresses
+    pub smtp: Option<SmtpConfig>,
+    /// Repositories and crates whose new security advisories are notified
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// Name used in the `registry` key of dependencies and in `/crate/:registry/...` URLs
+    pub name: String,
+    /// URL of the registry's git index
+    pub index: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the standard port of the chosen security
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<AccessToken>,
+    /// Sender of notification emails, e.g. `deps.rs <deps@example.com>`
+    pub from: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    Tls,
+    /// Plain text, only meant for local relays
+    None,
+}
+
+/// Subject to watch for new advisories, given either as `repo` or as `crate`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionConfig {
+    /// Repository as in `/repo/...` URLs, e.g. `github/deps-rs/deps.rs`
+    pub repo: Option<String>,
+    /// Branch, tag or commit of the repository, the default branch if unset
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Crate release as in `/crate/...` URLs, e.g. `hyper/0.14.2`
+    #[serde(rename = "crate")]
+    pub krate: Option<String>,
+    /// URL that notifications are posted to as JSON
+    pub webhook: Option<String>,
+    /// Address that notifications are mailed to
+    pub email: Option<String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        let path = match env::var_os("DEPS_RS_CONFIG") {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.to_string_lossy()))?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Collects the configured access tokens, letting `DEPS_RS_TOKEN_<SITE>` environment
+    /// variables take precedence over the config file.
+    pub fn site_tokens(&self) -> SiteTokens {
+        self.site_secrets(&self.tokens, "DEPS_RS_TOKEN")
+    }
+
+    /// Collects the secrets that push webhooks are verified with, letting
+    /// `DEPS_RS_WEBHOOK_SECRET_<SITE>` environment variables take precedence over the config file.
+    pub fn webhook_secrets(&self) -> SiteTokens {
+        self.site_secrets(&self.webhook_secrets, "DEPS_RS_WEBHOOK_SECRET")
+    }
+
+    fn site_secrets(&self, configured: &HashMap<String, AccessToken>, prefix: &str) -> SiteTokens {
+        let mut secrets = configured.clone();
+
+        let site_names = ["github", "gitlab", "bitbucket"]
+            .iter()
+            .copied()
+            .chain(self.sites.iter().map(|site| site.name.as_str()));
+
+        for name in site_names {
+            let var = format!(
+                "{}_{}",
+                prefix,
+                name.to_uppercase().replace(['-', '.'], "_")
+            );
+            if let Ok(secret) = env::var(var) {
+                secrets.insert(name.to_string(), AccessToken(secret));
+            }
+        }
+
+        SiteTokens(Arc::new(secrets))
+    }
+}
+
+/// A secret that is redacted from debug output and must only ever be sent in request headers
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct AccessToken(String);
+
+impl AccessToken {
+    pub fn secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AccessToken(<redacted>)")
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SiteTokens(Arc<HashMap<String, AccessToken>>);
+
+impl SiteTokens {
+    pub fn get(&self, site: &RepoSite) -> Option<&AccessToken> {
+        self.0.get(site.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::repo::CustomSiteKind;
+
+    #[test]
+    fn parse_custom_sites() {
+        let config: Config = toml::from_str(
+            r#"
+[[sites]]
+name = "corp-gitlab"
+kind = "gitlab"
+base_url = "https://gitlab.corp.example"
+raw_url = "https://gitlab.corp.example/{qual}/{name}/-/raw/{ref}/{path}"
+
+[[sites]]
+name = "gitea"
+kind = "gitea"
+base_url = "https://gitea.example"
+raw_url = "https://gitea.example/{qual}/{name}/raw/{ref}/{path}"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.sites.len(), 2);
+        assert_eq!(config.sites[0].kind, CustomSiteKind::Gitlab);
+        assert_eq!(config.sites[1].name, "gitea");
+    }
+
+    #[test]
+    fn parse_registries() {
+        let config: Config = toml::from_str(
+            r#"
+[[registries]]
+name = "corp"
+index = "https://git.corp.example/crates-index"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.registries.len(), 1);
+        assert_eq!(config.registries[0].name, "corp");
+        assert!(config.sites.is_empty());
+    }
+
+    #[test]
+    fn tokens_are_redacted() {
+        let config: Config = toml::from_str(
+            r#"
+[tokens]
+github = "ghp_secret"
+"#,
+        )
+        .unwrap();
+
+        let tokens = config.site_tokens();
+        let token = tokens.get(&RepoSite::Github).unwrap();
+        assert_eq!(token.secret(), "ghp_secret");
+        assert!(!format!("{:?}", config).contains("ghp_secret"));
+        assert!(!format!("{:?}", tokens).contains("ghp_secret"));
+        assert!(tokens.get(&RepoSite::Gitlab).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use std::{error, fmt, io, sync::Arc};
+
+use anyhow::Error;
+use derive_more::Display;
+use hyper::StatusCode;
+use relative_path::RelativePathBuf;
+use serde::Serialize;
+
+use crate::interactors::{crates::CrateNotFound, RateLimitExceeded};
+use crate::utils::cache::CacheError;
+
+/// Why an analysis could not be completed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    #[display(fmt = "not found")]
+    NotFound,
+    #[display(fmt = "parse error")]
+    ParseError,
+    #[display(fmt = "rate limited")]
+    RateLimited,
+    #[display(fmt = "timeout")]
+    Timeout,
+    #[display(fmt = "unknown")]
+    Unknown,
+}
+
+impl FailureKind {
+    pub(super) fn classify(err: &Error) -> FailureKind {
+        if err.downcast_ref::<ManifestError>().is_some() {
+            return FailureKind::ParseError;
+        }
+        if err.downcast_ref::<RateLimitExceeded>().is_some() {
+            return FailureKind::RateLimited;
+        }
+
+        for cause in err.chain() {
+            if let Some(failure) = cause.downcast_ref::<AnalysisFailure>() {
+                return failure.kind;
+            }
+            // the error of a cached query is only reachable through the cache's own error
+            if let Some(err) = cause.downcast_ref::<CacheError>() {
+                return FailureKind::classify(err.inner());
+            }
+
+            if cause.is::<CrateNotFound>() {
+                return FailureKind::NotFound;
+            }
+
+            if let Some(err) = cause.downcast_ref::<io::Error>() {
+                if err.kind() == io::ErrorKind::NotFound {
+                    return FailureKind::NotFound;
+                }
+            }
+
+            if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                if err.is_timeout() {
+                    return FailureKind::Timeout;
+                }
+
+                match err.status() {
+                    // private repositories can't be told apart from missing ones
+                    Some(StatusCode::NOT_FOUND) | Some(StatusCode::FORBIDDEN) => {
+                        return FailureKind::NotFound
+                    }
+                    Some(StatusCode::TOO_MANY_REQUESTS) => return FailureKind::RateLimited,
+                    _ => {}
+                }
+            }
+        }
+
+        FailureKind::Unknown
+    }
+}
+
+/// Context attached to errors raised while parsing a manifest
+#[derive(Debug, Display)]
+#[display(fmt = "failed to parse manifest at {}", path)]
+pub struct ManifestError {
+    pub path: RelativePathBuf,
+}
+
+/// Error of a failed analysis. Cheap to clone, so that it can be cached.
+#[derive(Clone, Debug)]
+pub struct AnalysisFailure {
+    pub kind: FailureKind,
+    error: Arc<Error>,
+}
+
+impl AnalysisFailure {
+    pub fn new(kind: FailureKind, error: Error) -> AnalysisFailure {
+        AnalysisFailure {
+            kind,
+            error: Arc::new(error),
+        }
+    }
+
+    /// The underlying error, including its causes when formatted with `{:#}`
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+impl From<Error> for AnalysisFailure {
+    fn from(err: Error) -> AnalysisFailure {
+        match err.downcast::<AnalysisFailure>() {
+            Ok(failure) => failure,
+            Err(err) => AnalysisFailure::new(FailureKind::classify(&err), err),
+        }
+    }
+}
+
+impl fmt::Display for AnalysisFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:#}", self.kind, self.error)
+    }
+}
+
+impl error::Error for AnalysisFailure {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context as _};
+
+    use super::*;
+
+    #[test]
+    fn classifies_manifest_errors() {
+        let err = anyhow!("expected `=`").context(ManifestError {
+            path: RelativePathBuf::from("Cargo.toml"),
+        });
+
+        let failure = AnalysisFailure::from(err);
+        assert_eq!(failure.kind, FailureKind::ParseError);
+        assert_eq!(
+            failure.to_string(),
+            "parse error: failed to parse manifest at Cargo.toml: expected `=`"
+        );
+    }
+
+    #[test]
+    fn keeps_existing_classification() {
+        let err = Error::new(AnalysisFailure::new(
+            FailureKind::NotFound,
+            anyhow!("no such release"),
+        ))
+        .context("while analyzing");
+
+        assert_eq!(FailureKind::classify(&err), FailureKind::NotFound);
+        assert_eq!(AnalysisFailure::from(err).kind, FailureKind::NotFound);
+    }
+
+    #[test]
+    fn classifies_missing_files() {
+        let result: Result<(), io::Error> = Err(io::ErrorKind::NotFound.into());
+        let err = result.context("failed to read Cargo.lock").unwrap_err();
+        assert_eq!(FailureKind::classify(&err), FailureKind::NotFound);
+    }
+
+    #[test]
+    fn classifies_rate_limits() {
+        let err = anyhow!("403 Forbidden")
+            .context(RateLimitExceeded)
+            .context("failed to retrieve Cargo.toml");
+        assert_eq!(FailureKind::classify(&err), FailureKind::RateLimited);
+
+        let err = Error::new(CrateNotFound("hyper".parse().unwrap())).context("while analyzing");
+        assert_eq!(FailureKind::classify(&err), FailureKind::NotFound);
+    }
+
+    #[test]
+    fn unclassified_errors_are_unknown() {
+        let result: Result<(), Error> = Err(anyhow!("boom")).context("crawling");
+        let failure = AnalysisFailure::from(result.unwrap_err());
+        assert_eq!(failure.kind, FailureKind::Unknown);
+    }
+}
@@ -1,32 +1,71 @@
 use anyhow::Error;
 use futures::StreamExt;
+use indexmap::IndexSet;
 
 use crate::{
-    engine::machines::analyzer::DependencyAnalyzer,
-    models::crates::{AnalyzedDependencies, CrateDep, CrateDeps, CrateName},
-    Engine,
+    engine::{
+        machines::{
+            analyzer::DependencyAnalyzer,
+            lockfile::LockfileAnalyzer,
+            tree::{DependencyTreeResolver, RegistryCrate},
+        },
+        Engine, FailureKind,
+    },
+    models::crates::{
+        AnalysisPolicy, AnalyzedDependencies, AnalyzedDependencyTree, AnalyzedLockfile, CrateDep,
+        CrateDeps, CrateName, LockedPackage,
+    },
 };
 
-fn filter_external((name, dep): (CrateName, CrateDep)) -> Option<CrateName> {
-    if dep.is_external() {
-        Some(name)
-    } else {
-        None
+fn filter_external((name, dep): (CrateName, CrateDep)) -> Option<RegistryCrate> {
+    match dep {
+        CrateDep::External(_) => Some((None, name)),
+        CrateDep::Registry(registry, _) => Some((Some(registry), name)),
+        _ => None,
     }
 }
 
 pub async fn analyze_dependencies(
     engine: Engine,
     deps: CrateDeps,
+    policy: &AnalysisPolicy,
 ) -> Result<AnalyzedDependencies, Error> {
-    let advisory_db = engine.fetch_advisory_db().await?;
-    let mut analyzer = DependencyAnalyzer::new(&deps, Some(advisory_db));
+    let advisory_db = engine.advisory_db()?;
+    let mut analyzer = DependencyAnalyzer::new(&deps, Some(advisory_db)).with_policy(policy);
+
+    let git_names: Vec<RegistryCrate> = deps
+        .main
+        .iter()
+        .chain(&deps.dev)
+        .chain(&deps.build)
+        .filter(|(_, dep)| matches!(dep, CrateDep::Git(_)))
+        .map(|(name, _)| (None, name.clone()))
+        .collect();
 
     let main_deps = deps.main.into_iter().filter_map(filter_external);
     let dev_deps = deps.dev.into_iter().filter_map(filter_external);
     let build_deps = deps.build.into_iter().filter_map(filter_external);
+    let platform_deps = deps
+        .platforms
+        .main
+        .into_iter()
+        .chain(deps.platforms.dev)
+        .chain(deps.platforms.build)
+        .flat_map(|(name, others)| {
+            others
+                .into_iter()
+                .map(move |other| (name.clone(), other.dep))
+        })
+        .filter_map(filter_external);
 
-    let deps_iter = main_deps.chain(dev_deps).chain(build_deps);
+    // dependencies on registries the server isn't configured for are left unresolved, and
+    // crates declared several times are only looked up once
+    let deps_iter: IndexSet<RegistryCrate> = main_deps
+        .chain(dev_deps)
+        .chain(build_deps)
+        .chain(platform_deps)
+        .filter(|(registry, _)| engine.knows_registry(registry.as_ref()))
+        .collect();
     let mut releases = engine.fetch_releases(deps_iter);
 
     while let Some(release) = releases.next().await {
@@ -34,5 +73,70 @@ pub async fn analyze_dependencies(
         analyzer.process(release)
     }
 
+    let mut git_releases = engine.fetch_releases(git_names);
+
+    while let Some(release) = git_releases.next().await {
+        // git dependencies don't have to be published to crates.io
+        if let Ok(release) = release {
+            analyzer.process_git(release)
+        }
+    }
+
     Ok(analyzer.finalize())
 }
+
+pub async fn analyze_lockfile(
+    engine: Engine,
+    packages: Vec<LockedPackage>,
+    policy: &AnalysisPolicy,
+) -> Result<AnalyzedLockfile, Error> {
+    let advisory_db = engine.advisory_db()?;
+
+    let names: IndexSet<CrateName> = packages.iter().map(|pkg| pkg.name.clone()).collect();
+    let mut analyzer = LockfileAnalyzer::new(packages, Some(advisory_db)).with_policy(policy);
+
+    // lockfiles are only checked against crates.io
+    let mut releases = engine.fetch_releases(names.into_iter().map(|name| (None, name)));
+
+    while let Some(release) = releases.next().await {
+        match release {
+            Ok(release) => analyzer.process(release),
+            // locked packages may come from git or be missing from the index, and can't be
+            // checked for yanked versions then
+            Err(err) if FailureKind::classify(&err) == FailureKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(analyzer.finalize())
+}
+
+pub async fn analyze_dependency_tree(
+    engine: Engine,
+    root: CrateName,
+    deps: CrateDeps,
+) -> Result<AnalyzedDependencyTree, Error> {
+    let advisory_db = engine.advisory_db()?;
+    let mut resolver = DependencyTreeResolver::new(&root, &deps, Some(advisory_db));
+
+    loop {
+        let names = resolver.names_of_interest();
+        if names.is_empty() {
+            break;
+        }
+
+        let mut releases = engine.fetch_releases(names);
+
+        while let Some(release) = releases.next().await {
+            match release {
+                Ok(release) => resolver.process(release),
+                // crates missing from the index or from an unknown registry are left out of
+                // the tree
+                Err(err) if FailureKind::classify(&err) == FailureKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    Ok(resolver.finalize())
+}
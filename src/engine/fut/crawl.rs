@@ -1,56 +1,140 @@
-use anyhow::Error;
+use anyhow::{Context as _, Error};
 use futures::{future::BoxFuture, stream::FuturesOrdered, FutureExt as _, StreamExt as _};
 use relative_path::RelativePathBuf;
-
-use crate::models::repo::RepoPath;
+use slog::warn;
 
 use crate::engine::{
-    machines::crawler::{ManifestCrawler, ManifestCrawlerOutput},
-    Engine,
+    machines::crawler::{
+        ManifestCrawler, ManifestCrawlerOutput, ManifestCrawlerStepOutput, WorkspaceGlob,
+    },
+    Engine, ManifestError, ManifestSource,
 };
 
+enum CrawlItem {
+    Manifest(RelativePathBuf, String),
+    Directories(WorkspaceGlob, Vec<String>),
+}
+
+type CrawlFuture = BoxFuture<'static, Result<CrawlItem, Error>>;
+
+fn retrieve_manifest(engine: Engine, source: ManifestSource, path: RelativePathBuf) -> CrawlFuture {
+    async move {
+        let contents = engine.retrieve_manifest_at_path(&source, &path).await?;
+        Ok(CrawlItem::Manifest(path, contents))
+    }
+    .boxed()
+}
+
+fn retrieve_directories(
+    engine: Engine,
+    source: ManifestSource,
+    glob: WorkspaceGlob,
+) -> CrawlFuture {
+    async move {
+        let directories = engine
+            .retrieve_directories_at_path(&source, &glob.dir)
+            .await?;
+        Ok(CrawlItem::Directories(glob, directories))
+    }
+    .boxed()
+}
+
 pub async fn crawl_manifest(
     engine: Engine,
-    repo_path: RepoPath,
+    source: ManifestSource,
     entry_point: RelativePathBuf,
 ) -> anyhow::Result<ManifestCrawlerOutput> {
     let mut crawler = ManifestCrawler::new();
-    let mut futures: FuturesOrdered<BoxFuture<'static, Result<(RelativePathBuf, String), Error>>> =
-        FuturesOrdered::new();
+    let mut futures: FuturesOrdered<CrawlFuture> = FuturesOrdered::new();
 
-    let engine2 = engine.clone();
-    let repo_path2 = repo_path.clone();
+    futures.push(retrieve_manifest(
+        engine.clone(),
+        source.clone(),
+        entry_point,
+    ));
 
-    let fut = async move {
-        let contents = engine2
-            .retrieve_manifest_at_path(&repo_path2, &entry_point)
-            .await?;
-        Ok((entry_point, contents))
+    while let Some(item) = futures.next().await {
+        let output: ManifestCrawlerStepOutput = match item? {
+            CrawlItem::Manifest(path, raw_manifest) => crawler
+                .step(path.clone(), raw_manifest)
+                .context(ManifestError { path })?,
+            CrawlItem::Directories(glob, directories) => crawler.expand_glob(&glob, directories),
+        };
+
+        for path in output.paths_of_interest {
+            futures.push(retrieve_manifest(engine.clone(), source.clone(), path));
+        }
+
+        for glob in output.globs_of_interest {
+            futures.push(retrieve_directories(engine.clone(), source.clone(), glob));
+        }
     }
-    .boxed();
 
-    futures.push(fut);
+    let output = crawler.finalize();
 
-    while let Some(item) = futures.next().await {
-        let (path, raw_manifest) = item?;
-        let output = crawler.step(path, raw_manifest)?;
+    for (path, name) in &output.unresolved {
+        warn!(
+            engine.logger,
+            "inherited dependency is missing from the workspace";
+            "source" => source.to_string(),
+            "manifest" => path.as_str(),
+            "dependency" => name.as_ref()
+        );
+    }
 
-        let engine = engine.clone();
-        let repo_path = repo_path.clone();
+    Ok(output)
+}
 
-        for path in output.paths_of_interest {
-            let engine = engine.clone();
-            let repo_path = repo_path.clone();
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
 
-            let fut = async move {
-                let contents = engine.retrieve_manifest_at_path(&repo_path, &path).await?;
-                Ok((path, contents))
-            }
-            .boxed();
+    use crates_index::Index;
+    use relative_path::RelativePath;
+    use slog::{o, Discard, Logger};
 
-            futures.push(fut);
-        }
-    }
+    use super::*;
+    use crate::config::SiteTokens;
+    use crate::utils::{advisory_db::AdvisoryDb, history::History, index::CrateIndex};
 
-    Ok(crawler.finalize())
+    #[tokio::test]
+    async fn crawls_local_checkouts() {
+        let root = std::env::temp_dir().join(format!("deps-rs-crawl-{}", std::process::id()));
+        fs::create_dir_all(root.join("crates/core")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.metadata.deps-rs]\npinned = [\"serde\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("crates/core/Cargo.toml"),
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n",
+        )
+        .unwrap();
+
+        let engine = Engine::new(
+            reqwest::Client::new(),
+            CrateIndex::CratesIo(Index::new(PathBuf::from("/nonexistent"))),
+            HashMap::new(),
+            AdvisoryDb::default(),
+            SiteTokens::default(),
+            History::in_memory().unwrap(),
+            Logger::root(Discard, o!()),
+        );
+        let source = ManifestSource::Local(Arc::new(root.clone()));
+        let output = crawl_manifest(
+            engine,
+            source,
+            RelativePath::new("/").to_relative_path_buf(),
+        )
+        .await
+        .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(output.crates.len(), 1);
+        let (name, deps) = output.crates.iter().next().unwrap();
+        assert_eq!(name.as_ref(), "core");
+        assert!(deps.main.contains_key("serde"));
+        assert!(output.policy.unwrap().pinned.contains("serde"));
+    }
 }
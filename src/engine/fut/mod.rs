@@ -1,5 +1,5 @@
 mod analyze;
 mod crawl;
 
-pub use self::analyze::analyze_dependencies;
+pub use self::analyze::{analyze_dependencies, analyze_dependency_tree, analyze_lockfile};
 pub use self::crawl::crawl_manifest;
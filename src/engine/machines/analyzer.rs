@@ -3,16 +3,31 @@ use std::sync::Arc;
 use rustsec::{
     cargo_lock,
     database::{self, Database},
+    Advisory,
 };
 use semver::Version;
 
 use crate::models::crates::{
-    AnalyzedDependencies, AnalyzedDependency, CrateDeps, CrateName, CrateRelease,
+    AnalysisPolicy, AnalyzedDependencies, AnalyzedDependency, CrateDeps, CrateName, CrateRelease,
 };
 
+/// Returns the advisories that affect the given version of a crate
+pub fn query_vulnerabilities(db: &Database, name: &CrateName, ver: &Version) -> Vec<Advisory> {
+    let name: cargo_lock::Name = name.as_ref().parse().unwrap();
+    let version: cargo_lock::Version = ver.to_string().parse().unwrap();
+    let query = database::Query::new().package_version(name, version);
+
+    db.query(&query)
+        .into_iter()
+        .filter(|vuln| !vuln.metadata.yanked)
+        .map(|v| v.to_owned())
+        .collect()
+}
+
 pub struct DependencyAnalyzer {
     deps: AnalyzedDependencies,
     advisory_db: Option<Arc<Database>>,
+    policy: AnalysisPolicy,
 }
 
 impl DependencyAnalyzer {
@@ -20,7 +35,43 @@ impl DependencyAnalyzer {
         DependencyAnalyzer {
             deps: AnalyzedDependencies::new(deps),
             advisory_db,
+            policy: AnalysisPolicy::default(),
+        }
+    }
+
+    /// Leaves out the dependencies that the policy ignores, and marks the pinned ones
+    pub fn with_policy(mut self, policy: &AnalysisPolicy) -> DependencyAnalyzer {
+        let deps = &mut self.deps;
+
+        if policy.exclude_dev {
+            deps.ignored
+                .extend(deps.dev.drain(..).map(|(name, _)| name));
+        }
+        for name in &policy.ignore {
+            let mut removed = deps.git.shift_remove(name).is_some();
+            for kind in [&mut deps.main, &mut deps.dev, &mut deps.build].iter_mut() {
+                removed |= kind.shift_remove(name).is_some();
+            }
+            if removed {
+                deps.ignored.insert(name.clone());
+            }
+        }
+
+        for (name, dep) in deps
+            .main
+            .iter_mut()
+            .chain(deps.dev.iter_mut())
+            .chain(deps.build.iter_mut())
+        {
+            let pinned = policy.pinned.contains(name);
+            dep.pinned = pinned;
+            for other in &mut dep.platforms {
+                other.pinned = pinned;
+            }
         }
+
+        self.policy = policy.clone();
+        self
     }
 
     fn process_single(
@@ -28,6 +79,7 @@ impl DependencyAnalyzer {
         dep: &mut AnalyzedDependency,
         ver: &Version,
         advisory_db: Option<&Database>,
+        policy: &AnalysisPolicy,
     ) {
         if dep.required.matches(&ver) {
             if let Some(ref mut current_latest_that_matches) = dep.latest_that_matches {
@@ -38,20 +90,18 @@ impl DependencyAnalyzer {
                 dep.latest_that_matches = Some(ver.clone());
             }
 
-            let name: cargo_lock::Name = name.as_ref().parse().unwrap();
-            let version: cargo_lock::Version = ver.to_string().parse().unwrap();
-            let query = database::Query::new().package_version(name, version);
-
-            if let Some(db) = advisory_db {
-                let vulnerabilities: Vec<_> = db
-                    .query(&query)
-                    .into_iter()
-                    .filter(|vuln| !vuln.metadata.yanked)
-                    .map(|v| v.to_owned())
-                    .collect();
+            // the advisory database only covers crates.io
+            if let (Some(db), None) = (advisory_db, &dep.registry) {
+                let (ignored, vulnerabilities): (Vec<_>, Vec<_>) =
+                    query_vulnerabilities(db, name, ver)
+                        .into_iter()
+                        .partition(|advisory| policy.ignores_advisory(advisory));
                 if !vulnerabilities.is_empty() {
                     dep.vulnerabilities = vulnerabilities;
                 }
+                if !ignored.is_empty() {
+                    dep.ignored_vulnerabilities = ignored;
+                }
             }
         }
         if ver.pre.is_empty() {
@@ -68,29 +118,52 @@ impl DependencyAnalyzer {
     pub fn process<I: IntoIterator<Item = CrateRelease>>(&mut self, releases: I) {
         let advisory_db = self.advisory_db.as_ref().map(|r| r.as_ref());
         for release in releases.into_iter().filter(|r| !r.yanked) {
-            if let Some(main_dep) = self.deps.main.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(
-                    &release.name,
-                    main_dep,
-                    &release.version,
-                    advisory_db,
-                )
-            }
-            if let Some(dev_dep) = self.deps.dev.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(
-                    &release.name,
-                    dev_dep,
-                    &release.version,
-                    advisory_db,
-                )
+            let deps = &mut self.deps;
+            for kind in [&mut deps.main, &mut deps.dev, &mut deps.build].iter_mut() {
+                let dep = match kind.get_mut(&release.name) {
+                    Some(dep) => dep,
+                    None => continue,
+                };
+                if dep.registry == release.registry {
+                    DependencyAnalyzer::process_single(
+                        &release.name,
+                        dep,
+                        &release.version,
+                        advisory_db,
+                        &self.policy,
+                    )
+                }
+                for other in dep
+                    .platforms
+                    .iter_mut()
+                    .filter(|other| other.registry == release.registry)
+                {
+                    DependencyAnalyzer::process_single(
+                        &release.name,
+                        other,
+                        &release.version,
+                        advisory_db,
+                        &self.policy,
+                    )
+                }
             }
-            if let Some(build_dep) = self.deps.build.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(
-                    &release.name,
-                    build_dep,
-                    &release.version,
-                    advisory_db,
-                )
+        }
+    }
+
+    /// Records the crates.io releases of crates that are used as git dependencies
+    pub fn process_git<I: IntoIterator<Item = CrateRelease>>(&mut self, releases: I) {
+        for release in releases {
+            let git_dep = match self.deps.git.get_mut(&release.name) {
+                Some(git_dep) => git_dep,
+                None => continue,
+            };
+
+            if release.registry.is_none()
+                && !release.yanked
+                && release.version.pre.is_empty()
+                && git_dep.latest.as_ref() < Some(&release.version)
+            {
+                git_dep.latest = Some(release.version);
             }
         }
     }
@@ -102,7 +175,7 @@ impl DependencyAnalyzer {
 
 #[cfg(test)]
 mod tests {
-    use crate::models::crates::{CrateDep, CrateDeps, CrateRelease};
+    use crate::models::crates::{CrateDep, CrateDeps, CrateRelease, GitDep, GitPin};
 
     use super::*;
 
@@ -121,12 +194,14 @@ mod tests {
                 version: "0.10.0".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
             CrateRelease {
                 name: "hyper".parse().unwrap(),
                 version: "0.10.1".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
         ]);
 
@@ -157,18 +232,21 @@ mod tests {
                 version: "0.10.0".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
             CrateRelease {
                 name: "hyper".parse().unwrap(),
                 version: "0.10.1".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
             CrateRelease {
                 name: "hyper".parse().unwrap(),
                 version: "0.11.0".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
         ]);
 
@@ -199,12 +277,14 @@ mod tests {
                 version: "0.10.0".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
             CrateRelease {
                 name: "hyper".parse().unwrap(),
                 version: "0.10.1".parse().unwrap(),
                 deps: Default::default(),
                 yanked: true,
+                registry: None,
             },
         ]);
 
@@ -235,12 +315,14 @@ mod tests {
                 version: "0.10.0".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
             CrateRelease {
                 name: "hyper".parse().unwrap(),
                 version: "0.10.1-alpha".parse().unwrap(),
                 deps: Default::default(),
                 yanked: false,
+                registry: None,
             },
         ]);
 
@@ -255,4 +337,129 @@ mod tests {
             Some("0.10.0".parse().unwrap())
         );
     }
+
+    #[test]
+    fn only_matches_releases_from_the_same_registry() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert(
+            "auth".parse().unwrap(),
+            CrateDep::Registry("corp".parse().unwrap(), "^1.0.0".parse().unwrap()),
+        );
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process(vec![
+            CrateRelease {
+                name: "auth".parse().unwrap(),
+                version: "2.0.0".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+            CrateRelease {
+                name: "auth".parse().unwrap(),
+                version: "1.1.0".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: Some("corp".parse().unwrap()),
+            },
+        ]);
+
+        let analyzed = analyzer.finalize();
+        let auth = analyzed.main.get("auth").unwrap();
+
+        assert_eq!(auth.latest_that_matches, Some("1.1.0".parse().unwrap()));
+        assert_eq!(auth.latest, Some("1.1.0".parse().unwrap()));
+        assert_eq!(auth.deps_rs_path("auth"), "/crate/corp/auth/1.1.0");
+    }
+
+    #[test]
+    fn applies_policy() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert(
+            "hyper".parse().unwrap(),
+            CrateDep::External("^0.10.0".parse().unwrap()),
+        );
+        deps.main.insert(
+            "openssl".parse().unwrap(),
+            CrateDep::External("^0.9.0".parse().unwrap()),
+        );
+        deps.dev.insert(
+            "criterion".parse().unwrap(),
+            CrateDep::External("^0.3.0".parse().unwrap()),
+        );
+
+        let policy = AnalysisPolicy {
+            ignore: vec!["openssl".parse().unwrap()].into_iter().collect(),
+            exclude_dev: true,
+            pinned: vec!["hyper".parse().unwrap()].into_iter().collect(),
+            ..AnalysisPolicy::default()
+        };
+        let mut analyzer = DependencyAnalyzer::new(&deps, None).with_policy(&policy);
+        analyzer.process(vec![
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "0.10.1".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "0.11.0".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.count_total(), 1);
+        assert_eq!(analyzed.count_outdated(), 0);
+        assert!(analyzed.dev.is_empty());
+        assert_eq!(analyzed.ignored.len(), 2);
+        assert!(analyzed.ignored.contains("openssl"));
+        assert!(analyzed.ignored.contains("criterion"));
+
+        let hyper = analyzed.main.get("hyper").unwrap();
+        assert!(hyper.pinned && hyper.is_behind());
+        assert_eq!(analyzed.pinned_behind().count(), 1);
+    }
+
+    #[test]
+    fn tracks_published_releases_of_git_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert(
+            "hyper".parse().unwrap(),
+            CrateDep::Git(GitDep {
+                url: "https://github.com/hyperium/hyper".to_string(),
+                pin: GitPin::DefaultBranch,
+            }),
+        );
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process_git(vec![
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "0.14.2".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "1.0.0-rc.1".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+        ]);
+
+        let analyzed = analyzer.finalize();
+        assert!(analyzed.main.is_empty());
+
+        let hyper = analyzed.git.get("hyper").unwrap();
+        assert!(hyper.is_published());
+        assert_eq!(hyper.latest, Some("0.14.2".parse().unwrap()));
+    }
 }
@@ -1,30 +1,108 @@
 use std::collections::HashMap;
 
 use anyhow::Error;
-use indexmap::IndexMap;
-use relative_path::RelativePathBuf;
+use indexmap::{IndexMap, IndexSet};
+use relative_path::{RelativePath, RelativePathBuf};
 
-use crate::models::crates::{CrateDep, CrateDeps, CrateManifest, CrateName};
+use crate::models::crates::{
+    AnalysisPolicy, CrateDep, CrateDeps, CrateManifest, CrateName, PlatformDep, WorkspaceDeps,
+};
 use crate::parsers::manifest::parse_manifest_toml;
+use crate::parsers::policy::parse_manifest_policy;
 
 pub struct ManifestCrawlerOutput {
     pub crates: IndexMap<CrateName, CrateDeps>,
+    /// Policy declared in the metadata of the entry point's manifest
+    pub policy: Option<AnalysisPolicy>,
+    /// Dependencies inherited from a workspace that doesn't define them, along with the
+    /// path of the manifest declaring them
+    pub unresolved: Vec<(RelativePathBuf, CrateName)>,
 }
 
 pub struct ManifestCrawlerStepOutput {
     pub paths_of_interest: Vec<RelativePathBuf>,
+    pub globs_of_interest: Vec<WorkspaceGlob>,
+}
+
+/// A workspace member pattern like `crates/*`, which needs a directory listing to be resolved
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkspaceGlob {
+    /// Directory whose subdirectories are matched against the pattern
+    pub dir: RelativePathBuf,
+    /// Wildcard pattern for a single path segment
+    pub pattern: String,
+    /// Remainder of the member path following the matched segment
+    pub suffix: RelativePathBuf,
+}
+
+impl WorkspaceGlob {
+    fn from_member(base_path: &RelativePath, member: &RelativePath) -> Option<WorkspaceGlob> {
+        let member = member.normalize();
+        let segments: Vec<&str> = member.as_str().split('/').collect();
+        let glob_index = segments.iter().position(|s| is_glob_pattern(s))?;
+
+        Some(WorkspaceGlob {
+            dir: base_path.join_normalized(segments[..glob_index].join("/")),
+            pattern: segments[glob_index].to_string(),
+            suffix: RelativePathBuf::from(segments[glob_index + 1..].join("/")),
+        })
+    }
+}
+
+fn is_glob_pattern(segment: &str) -> bool {
+    segment.contains(['*', '?'])
+}
+
+/// Matches `name` against a pattern supporting the `*` and `?` wildcards
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    backtrack = Some((bp, bn + 1));
+                    p = bp + 1;
+                    n = bn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 pub struct ManifestCrawler {
     manifests: HashMap<RelativePathBuf, CrateManifest>,
+    excluded: Vec<RelativePathBuf>,
+    workspace_deps: Option<(RelativePathBuf, WorkspaceDeps)>,
     leaf_crates: IndexMap<CrateName, CrateDeps>,
+    policy: Option<AnalysisPolicy>,
+    unresolved: Vec<(RelativePathBuf, CrateName)>,
 }
 
 impl ManifestCrawler {
     pub fn new() -> ManifestCrawler {
         ManifestCrawler {
             manifests: HashMap::new(),
+            excluded: Vec::new(),
+            workspace_deps: None,
             leaf_crates: IndexMap::new(),
+            policy: None,
+            unresolved: Vec::new(),
         }
     }
 
@@ -34,32 +112,74 @@ impl ManifestCrawler {
         raw_manifest: String,
     ) -> Result<ManifestCrawlerStepOutput, Error> {
         let manifest = parse_manifest_toml(&raw_manifest)?;
+        // the entry point is crawled first, and only its metadata configures the analysis
+        if self.manifests.is_empty() {
+            self.policy = parse_manifest_policy(&raw_manifest)?;
+        }
         self.manifests.insert(path.clone(), manifest.clone());
 
         let mut output = ManifestCrawlerStepOutput {
             paths_of_interest: vec![],
+            globs_of_interest: vec![],
         };
 
         match manifest {
             CrateManifest::Package(name, deps) => {
                 self.process_package(&path, name, deps, &mut output);
             }
-            CrateManifest::Workspace { members } => {
-                self.process_workspace(&path, &members, &mut output);
+            CrateManifest::Workspace {
+                members,
+                exclude,
+                workspace_deps,
+            } => {
+                self.register_workspace_deps(&path, workspace_deps);
+                self.process_workspace(&path, &members, &exclude, &mut output);
             }
             CrateManifest::Mixed {
                 name,
                 deps,
                 members,
+                exclude,
+                workspace_deps,
             } => {
+                self.register_workspace_deps(&path, workspace_deps);
                 self.process_package(&path, name, deps, &mut output);
-                self.process_workspace(&path, &members, &mut output);
+                self.process_workspace(&path, &members, &exclude, &mut output);
             }
         }
 
         Ok(output)
     }
 
+    /// Registers interest in the members matched by a workspace glob, given the names of the
+    /// subdirectories found in the glob's directory
+    pub fn expand_glob(
+        &mut self,
+        glob: &WorkspaceGlob,
+        subdirectories: Vec<String>,
+    ) -> ManifestCrawlerStepOutput {
+        let mut output = ManifestCrawlerStepOutput {
+            paths_of_interest: vec![],
+            globs_of_interest: vec![],
+        };
+
+        for subdirectory in subdirectories {
+            if !matches_glob(&glob.pattern, &subdirectory) {
+                continue;
+            }
+
+            let member = RelativePathBuf::from(subdirectory).join_normalized(&glob.suffix);
+            let full_path = glob.dir.join_normalized(&member);
+            if self.excluded.iter().any(|ex| full_path.starts_with(ex)) {
+                continue;
+            }
+
+            self.register_interest(&glob.dir, &member, &mut output);
+        }
+
+        output
+    }
+
     fn register_interest(
         &mut self,
         base_path: &RelativePathBuf,
@@ -72,6 +192,112 @@ impl ManifestCrawler {
         }
     }
 
+    fn register_workspace_deps(&mut self, base_path: &RelativePathBuf, deps: WorkspaceDeps) {
+        // only the outermost workspace is relevant for inheritance
+        if self.workspace_deps.is_none() {
+            self.workspace_deps = Some((base_path.clone(), deps));
+        }
+    }
+
+    /// Looks up the workspace definition of a dependency declared with `workspace = true`,
+    /// returning its crate name, its definition relative to `base_path` and its features
+    fn inherit(
+        &self,
+        base_path: &RelativePathBuf,
+        key: &CrateName,
+    ) -> Option<(CrateName, CrateDep, IndexSet<String>)> {
+        let (workspace_path, workspace_deps) = self.workspace_deps.as_ref()?;
+        let workspace_dep = workspace_deps.get(key)?;
+
+        let dep = match workspace_dep.dep.clone() {
+            // workspace paths are relative to the workspace root
+            CrateDep::Internal(path) => {
+                let full_path = workspace_path.join_normalized(path);
+                CrateDep::Internal(base_path.relative(full_path))
+            }
+            dep => dep,
+        };
+        Some((
+            workspace_dep.name.clone(),
+            dep,
+            workspace_dep.features.clone(),
+        ))
+    }
+
+    fn report_unresolved(&mut self, base_path: &RelativePathBuf, key: CrateName) {
+        let unresolved = (base_path.clone(), key);
+        if !self.unresolved.contains(&unresolved) {
+            self.unresolved.push(unresolved);
+        }
+    }
+
+    /// Replaces dependencies declared with `workspace = true` by their workspace definition,
+    /// combining the features enabled on both sides. Those that the workspace does not define
+    /// are dropped and reported in the output.
+    fn resolve_inherited(
+        &mut self,
+        base_path: &RelativePathBuf,
+        deps: IndexMap<CrateName, CrateDep>,
+        targets: &mut IndexMap<CrateName, String>,
+        optional: &mut IndexSet<CrateName>,
+        features: &mut IndexMap<CrateName, IndexSet<String>>,
+        platforms: &mut IndexMap<CrateName, Vec<PlatformDep>>,
+    ) -> IndexMap<CrateName, CrateDep> {
+        let mut resolved = IndexMap::new();
+
+        for (key, dep) in deps {
+            let mut others = Vec::new();
+            for mut other in platforms.shift_remove(&key).unwrap_or_default() {
+                if other.dep == CrateDep::Inherited {
+                    match self.inherit(base_path, &key) {
+                        Some((_, dep, mut dep_features)) => {
+                            dep_features.extend(other.features);
+                            other.dep = dep;
+                            other.features = dep_features;
+                        }
+                        None => {
+                            self.report_unresolved(base_path, key.clone());
+                            continue;
+                        }
+                    }
+                }
+                others.push(other);
+            }
+
+            let (name, dep) = if dep == CrateDep::Inherited {
+                let (name, dep, mut dep_features) = match self.inherit(base_path, &key) {
+                    Some(inherited) => inherited,
+                    None => {
+                        self.report_unresolved(base_path, key);
+                        continue;
+                    }
+                };
+
+                // renamed dependencies are keyed by their crate name once resolved
+                if let Some(target) = targets.remove(&key) {
+                    targets.insert(name.clone(), target);
+                }
+                if optional.remove(&key) {
+                    optional.insert(name.clone());
+                }
+                dep_features.extend(features.remove(&key).unwrap_or_default());
+                if !dep_features.is_empty() {
+                    features.insert(name.clone(), dep_features);
+                }
+                (name, dep)
+            } else {
+                (key, dep)
+            };
+
+            if !others.is_empty() {
+                platforms.insert(name.clone(), others);
+            }
+            resolved.insert(name, dep);
+        }
+
+        resolved
+    }
+
     fn process_package(
         &mut self,
         base_path: &RelativePathBuf,
@@ -79,6 +305,41 @@ impl ManifestCrawler {
         deps: CrateDeps,
         output: &mut ManifestCrawlerStepOutput,
     ) {
+        let mut targets = deps.targets;
+        let mut optional = deps.optional;
+        let mut features = deps.features;
+        let mut platforms = deps.platforms;
+        let deps = CrateDeps {
+            main: self.resolve_inherited(
+                base_path,
+                deps.main,
+                &mut targets.main,
+                &mut optional.main,
+                &mut features.main,
+                &mut platforms.main,
+            ),
+            dev: self.resolve_inherited(
+                base_path,
+                deps.dev,
+                &mut targets.dev,
+                &mut optional.dev,
+                &mut features.dev,
+                &mut platforms.dev,
+            ),
+            build: self.resolve_inherited(
+                base_path,
+                deps.build,
+                &mut targets.build,
+                &mut optional.build,
+                &mut features.build,
+                &mut platforms.build,
+            ),
+            targets,
+            optional,
+            features,
+            platforms,
+        };
+
         for (_, dep) in deps
             .main
             .iter()
@@ -97,11 +358,16 @@ impl ManifestCrawler {
         &mut self,
         base_path: &RelativePathBuf,
         members: &[RelativePathBuf],
+        exclude: &[RelativePathBuf],
         output: &mut ManifestCrawlerStepOutput,
     ) {
+        self.excluded
+            .extend(exclude.iter().map(|path| base_path.join_normalized(path)));
+
         for path in members {
-            if !path.ends_with("*") {
-                self.register_interest(base_path, path, output);
+            match WorkspaceGlob::from_member(base_path, path) {
+                Some(glob) => output.globs_of_interest.push(glob),
+                None => self.register_interest(base_path, path, output),
             }
         }
     }
@@ -109,6 +375,8 @@ impl ManifestCrawler {
     pub fn finalize(self) -> ManifestCrawlerOutput {
         ManifestCrawlerOutput {
             crates: self.leaf_crates,
+            policy: self.policy,
+            unresolved: self.unresolved,
         }
     }
 }
@@ -237,6 +505,126 @@ members = [
         let step_output = crawler.step("".into(), manifest.to_string()).unwrap();
         assert_eq!(step_output.paths_of_interest.len(), 1);
         assert_eq!(step_output.paths_of_interest[0].as_str(), "lib");
+        assert_eq!(step_output.globs_of_interest.len(), 1);
+        assert_eq!(step_output.globs_of_interest[0].dir.as_str(), "tests");
+        assert_eq!(step_output.globs_of_interest[0].pattern, "*");
+        assert_eq!(step_output.globs_of_interest[0].suffix.as_str(), "");
+
+        let expand_output = crawler.expand_glob(
+            &step_output.globs_of_interest[0],
+            vec!["foo".to_string(), "bar".to_string()],
+        );
+        assert_eq!(expand_output.paths_of_interest.len(), 2);
+        assert_eq!(expand_output.paths_of_interest[0].as_str(), "tests/foo");
+        assert_eq!(expand_output.paths_of_interest[1].as_str(), "tests/bar");
+    }
+
+    #[test]
+    fn glob_workspace_manifest_with_excludes() {
+        let manifest = r#"
+[workspace]
+members = ["crates/tokio-*", "examples/*/impl"]
+exclude = ["crates/tokio-experimental"]
+"#;
+        let mut crawler = ManifestCrawler::new();
+        let step_output = crawler.step("".into(), manifest.to_string()).unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 0);
+        assert_eq!(step_output.globs_of_interest.len(), 2);
+
+        let expand_output = crawler.expand_glob(
+            &step_output.globs_of_interest[0],
+            vec![
+                "tokio-util".to_string(),
+                "tokio-experimental".to_string(),
+                "mio".to_string(),
+            ],
+        );
+        assert_eq!(expand_output.paths_of_interest.len(), 1);
+        assert_eq!(
+            expand_output.paths_of_interest[0].as_str(),
+            "crates/tokio-util"
+        );
+
+        let expand_output =
+            crawler.expand_glob(&step_output.globs_of_interest[1], vec!["echo".to_string()]);
+        assert_eq!(expand_output.paths_of_interest.len(), 1);
+        assert_eq!(
+            expand_output.paths_of_interest[0].as_str(),
+            "examples/echo/impl"
+        );
+    }
+
+    #[test]
+    fn workspace_dependency_inheritance() {
+        let workspace_manifest = r#"
+[workspace]
+members = ["crates/app"]
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["rc"] }
+futures_legacy = { version = "0.1", package = "futures" }
+app-core = { path = "crates/core" }
+"#;
+
+        let app_manifest = r#"
+[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true, features = ["derive"] }
+futures_legacy = { workspace = true }
+app-core.workspace = true
+undeclared = { workspace = true }
+
+[dev-dependencies]
+serde.workspace = true
+"#;
+
+        let mut crawler = ManifestCrawler::new();
+        let step_output = crawler
+            .step("".into(), workspace_manifest.to_string())
+            .unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 1);
+        let step_output = crawler
+            .step("crates/app".into(), app_manifest.to_string())
+            .unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 1);
+        assert_eq!(step_output.paths_of_interest[0].as_str(), "crates/core");
+
+        let output = crawler.finalize();
+        let app = &output.crates["app"];
+        assert_eq!(app.main.len(), 3);
+        assert_eq!(
+            app.main.get("serde").unwrap(),
+            &CrateDep::External(VersionReq::parse("1.0").unwrap())
+        );
+        assert_eq!(
+            app.main.get("futures").unwrap(),
+            &CrateDep::External(VersionReq::parse("0.1").unwrap())
+        );
+        assert_eq!(
+            app.main.get("app-core").unwrap(),
+            &CrateDep::Internal(RelativePath::new("../core").to_relative_path_buf())
+        );
+        assert_eq!(app.dev.len(), 1);
+        assert!(app.features.main["serde"].contains("derive"));
+        assert!(app.features.main["serde"].contains("rc"));
+        assert_eq!(app.features.dev["serde"].len(), 1);
+        assert_eq!(
+            output.unresolved,
+            vec![("crates/app".into(), "undeclared".parse().unwrap())]
+        );
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(matches_glob("*", "anything"));
+        assert!(matches_glob("tokio-*", "tokio-util"));
+        assert!(!matches_glob("tokio-*", "mio"));
+        assert!(matches_glob("*-sys", "openssl-sys"));
+        assert!(matches_glob("a?c", "abc"));
+        assert!(!matches_glob("a?c", "ac"));
+        assert!(matches_glob("*a*b", "xaxxab"));
     }
 
     #[test]
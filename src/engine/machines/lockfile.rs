@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use indexmap::IndexSet;
+use rustsec::database::Database;
+
+use crate::engine::machines::analyzer::query_vulnerabilities;
+use crate::models::crates::{
+    AnalysisPolicy, AnalyzedLockedPackage, AnalyzedLockfile, CrateName, CrateRelease, LockedPackage,
+};
+
+pub struct LockfileAnalyzer {
+    packages: Vec<AnalyzedLockedPackage>,
+    ignored: IndexSet<CrateName>,
+}
+
+impl LockfileAnalyzer {
+    pub fn new(
+        packages: Vec<LockedPackage>,
+        advisory_db: Option<Arc<Database>>,
+    ) -> LockfileAnalyzer {
+        let packages = packages
+            .into_iter()
+            .map(|package| {
+                let mut analyzed = AnalyzedLockedPackage::new(package);
+                if let Some(ref db) = advisory_db {
+                    analyzed.vulnerabilities =
+                        query_vulnerabilities(db, &analyzed.name, &analyzed.version);
+                }
+                analyzed
+            })
+            .collect();
+
+        LockfileAnalyzer {
+            packages,
+            ignored: IndexSet::new(),
+        }
+    }
+
+    /// Leaves out the packages that the policy ignores, and sets ignored advisories apart.
+    ///
+    /// Lockfiles don't tell dev-dependencies apart, so `exclude-dev` doesn't apply to them.
+    pub fn with_policy(mut self, policy: &AnalysisPolicy) -> LockfileAnalyzer {
+        let ignored = &mut self.ignored;
+        self.packages.retain(|package| {
+            let is_ignored = policy.ignore.contains(&package.name);
+            if is_ignored {
+                ignored.insert(package.name.clone());
+            }
+            !is_ignored
+        });
+
+        for package in &mut self.packages {
+            let (ignored, vulnerabilities) = package
+                .vulnerabilities
+                .drain(..)
+                .partition(|advisory| policy.ignores_advisory(advisory));
+            package.vulnerabilities = vulnerabilities;
+            package.ignored_vulnerabilities = ignored;
+        }
+
+        self
+    }
+
+    pub fn process<I: IntoIterator<Item = CrateRelease>>(&mut self, releases: I) {
+        for release in releases.into_iter().filter(|r| r.yanked) {
+            for package in &mut self.packages {
+                if package.name == release.name && package.version == release.version {
+                    package.yanked = true;
+                }
+            }
+        }
+    }
+
+    pub fn finalize(self) -> AnalyzedLockfile {
+        AnalyzedLockfile {
+            packages: self.packages,
+            ignored: self.ignored,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_yanked_locked_versions() {
+        let mut analyzer = LockfileAnalyzer::new(
+            vec![
+                LockedPackage {
+                    name: "hyper".parse().unwrap(),
+                    version: "0.10.1".parse().unwrap(),
+                },
+                LockedPackage {
+                    name: "hyper".parse().unwrap(),
+                    version: "0.10.0".parse().unwrap(),
+                },
+            ],
+            None,
+        );
+        analyzer.process(vec![
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "0.10.0".parse().unwrap(),
+                deps: Default::default(),
+                yanked: false,
+                registry: None,
+            },
+            CrateRelease {
+                name: "hyper".parse().unwrap(),
+                version: "0.10.1".parse().unwrap(),
+                deps: Default::default(),
+                yanked: true,
+                registry: None,
+            },
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert!(analyzed.packages[0].yanked);
+        assert!(!analyzed.packages[1].yanked);
+        assert_eq!(analyzed.count_yanked(), 1);
+        assert_eq!(analyzed.count_insecure(), 0);
+    }
+
+    #[test]
+    fn records_ignored_locked_packages() {
+        let policy = AnalysisPolicy {
+            ignore: vec!["openssl-sys".parse().unwrap()].into_iter().collect(),
+            ..AnalysisPolicy::default()
+        };
+        let analyzer = LockfileAnalyzer::new(
+            vec![
+                LockedPackage {
+                    name: "openssl-sys".parse().unwrap(),
+                    version: "0.9.60".parse().unwrap(),
+                },
+                LockedPackage {
+                    name: "hyper".parse().unwrap(),
+                    version: "0.14.0".parse().unwrap(),
+                },
+            ],
+            None,
+        )
+        .with_policy(&policy);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.packages.len(), 1);
+        assert_eq!(analyzed.packages[0].name.as_ref(), "hyper");
+        assert!(analyzed.ignored.contains("openssl-sys"));
+    }
+}
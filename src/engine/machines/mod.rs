@@ -1,2 +1,4 @@
 pub mod analyzer;
 pub mod crawler;
+pub mod lockfile;
+pub mod tree;
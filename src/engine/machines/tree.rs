@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use rustsec::database::Database;
+use semver::{Version, VersionReq};
+
+use crate::engine::machines::analyzer::query_vulnerabilities;
+use crate::models::crates::{
+    AnalyzedDependency, AnalyzedDependencyTree, CrateDep, CrateDeps, CrateName, CrateRelease,
+    RegistryName, TransitiveDependency,
+};
+
+/// A crate name qualified by its alternate registry, `None` meaning crates.io
+pub type RegistryCrate = (Option<RegistryName>, CrateName);
+
+/// Upper bound on the number of resolved nodes, protecting against pathological graphs
+const MAX_TREE_SIZE: usize = 2000;
+
+struct PendingDependency {
+    name: CrateName,
+    registry: Option<RegistryName>,
+    required: VersionReq,
+    path: Vec<CrateName>,
+}
+
+/// Resolves the transitive dependency graph of a crate breadth-first, picking the newest
+/// release that satisfies the requirement at every node.
+///
+/// Releases are supplied in rounds: `names_of_interest` returns the crates whose releases are
+/// needed to make progress, which are then passed to `process`.
+pub struct DependencyTreeResolver {
+    advisory_db: Option<Arc<Database>>,
+    releases: HashMap<RegistryCrate, Vec<CrateRelease>>,
+    requested: HashSet<RegistryCrate>,
+    pending: VecDeque<PendingDependency>,
+    resolved: HashSet<(CrateName, Version)>,
+    dependencies: Vec<TransitiveDependency>,
+}
+
+impl DependencyTreeResolver {
+    pub fn new(
+        root: &CrateName,
+        deps: &CrateDeps,
+        advisory_db: Option<Arc<Database>>,
+    ) -> DependencyTreeResolver {
+        let mut resolver = DependencyTreeResolver {
+            advisory_db,
+            releases: HashMap::new(),
+            requested: HashSet::new(),
+            pending: VecDeque::new(),
+            resolved: HashSet::new(),
+            dependencies: Vec::new(),
+        };
+        resolver.enqueue(deps, vec![root.clone()]);
+        resolver
+    }
+
+    fn enqueue(&mut self, deps: &CrateDeps, path: Vec<CrateName>) {
+        // dev-dependencies are not part of the tree of dependent crates, and optional
+        // dependencies are only pulled in when a feature enables them
+        let main = deps
+            .main
+            .iter()
+            .filter(|(name, _)| !deps.optional.main.contains(*name));
+        let build = deps
+            .build
+            .iter()
+            .filter(|(name, _)| !deps.optional.build.contains(*name));
+
+        let platforms = deps
+            .platforms
+            .main
+            .iter()
+            .chain(&deps.platforms.build)
+            .flat_map(|(name, others)| others.iter().map(move |other| (name, other)))
+            .filter(|(_, other)| !other.optional)
+            .map(|(name, other)| (name, &other.dep));
+
+        for (name, dep) in main.chain(build).chain(platforms) {
+            let (registry, required) = match dep {
+                CrateDep::External(required) => (None, required),
+                CrateDep::Registry(registry, required) => (Some(registry), required),
+                _ => continue,
+            };
+            self.pending.push_back(PendingDependency {
+                name: name.clone(),
+                registry: registry.cloned(),
+                required: required.clone(),
+                path: path.clone(),
+            });
+        }
+    }
+
+    pub fn process<I: IntoIterator<Item = CrateRelease>>(&mut self, releases: I) {
+        for release in releases {
+            self.releases
+                .entry((release.registry.clone(), release.name.clone()))
+                .or_default()
+                .push(release);
+        }
+    }
+
+    /// Resolves as much of the graph as the known releases allow, returning the names of the
+    /// crates whose releases are needed next. An empty result means resolution is complete.
+    pub fn names_of_interest(&mut self) -> Vec<RegistryCrate> {
+        let mut names = Vec::new();
+        let mut blocked = VecDeque::new();
+
+        while let Some(pending) = self.pending.pop_front() {
+            if self.dependencies.len() >= MAX_TREE_SIZE {
+                self.pending.clear();
+                break;
+            }
+
+            let key = (pending.registry.clone(), pending.name.clone());
+            match self.releases.get(&key) {
+                Some(releases) => {
+                    let advisory_db = self.advisory_db.as_deref();
+                    let resolved =
+                        Self::resolve(&mut self.resolved, advisory_db, &pending, releases);
+                    if let Some((dependency, deps)) = resolved {
+                        let mut path = pending.path;
+                        path.push(pending.name);
+                        self.dependencies.push(dependency);
+                        self.enqueue(&deps, path);
+                    }
+                }
+                None => {
+                    if self.requested.insert(key.clone()) {
+                        names.push(key);
+                    }
+                    blocked.push_back(pending);
+                }
+            }
+        }
+
+        self.pending = blocked;
+
+        // crates missing from the index would otherwise block resolution forever
+        if names.is_empty() {
+            self.pending.clear();
+        }
+
+        names
+    }
+
+    fn resolve(
+        resolved: &mut HashSet<(CrateName, Version)>,
+        advisory_db: Option<&Database>,
+        pending: &PendingDependency,
+        releases: &[CrateRelease],
+    ) -> Option<(TransitiveDependency, CrateDeps)> {
+        let mut analyzed = AnalyzedDependency::new(pending.required.clone());
+        analyzed.registry = pending.registry.clone();
+        let mut selected: Option<&CrateRelease> = None;
+
+        for release in releases.iter().filter(|r| !r.yanked) {
+            if pending.required.matches(&release.version)
+                && selected.map(|s| &s.version) < Some(&release.version)
+            {
+                selected = Some(release);
+            }
+            if release.version.pre.is_empty() && analyzed.latest.as_ref() < Some(&release.version) {
+                analyzed.latest = Some(release.version.clone());
+            }
+        }
+
+        let selected = selected?;
+        if !resolved.insert((selected.name.clone(), selected.version.clone())) {
+            return None;
+        }
+
+        analyzed.latest_that_matches = Some(selected.version.clone());
+        // the advisory database only covers crates.io
+        if let (Some(db), None) = (advisory_db, &pending.registry) {
+            analyzed.vulnerabilities = query_vulnerabilities(db, &selected.name, &selected.version);
+        }
+
+        let dependency = TransitiveDependency {
+            path: pending.path.clone(),
+            name: pending.name.clone(),
+            analyzed,
+        };
+
+        Some((dependency, selected.deps.clone()))
+    }
+
+    pub fn finalize(self) -> AnalyzedDependencyTree {
+        AnalyzedDependencyTree {
+            dependencies: self.dependencies,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(name: &str, version: &str, deps: &[(&str, &str)]) -> CrateRelease {
+        let mut crate_deps = CrateDeps::default();
+        for (dep_name, req) in deps {
+            crate_deps.main.insert(
+                dep_name.parse().unwrap(),
+                CrateDep::External(req.parse().unwrap()),
+            );
+        }
+
+        CrateRelease {
+            name: name.parse().unwrap(),
+            version: version.parse().unwrap(),
+            deps: crate_deps,
+            yanked: false,
+            registry: None,
+        }
+    }
+
+    #[test]
+    fn resolves_transitive_dependencies_with_paths() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert(
+            "hyper".parse().unwrap(),
+            CrateDep::External("^0.14".parse().unwrap()),
+        );
+        deps.dev.insert(
+            "tokio-test".parse().unwrap(),
+            CrateDep::External("^0.4".parse().unwrap()),
+        );
+
+        let mut resolver = DependencyTreeResolver::new(&"reqwest".parse().unwrap(), &deps, None);
+
+        let names = resolver.names_of_interest();
+        assert_eq!(names, vec![(None, "hyper".parse::<CrateName>().unwrap())]);
+        resolver.process(vec![
+            release("hyper", "0.14.1", &[("h2", "^0.2"), ("bytes", "^1")]),
+            release("hyper", "0.14.2", &[("h2", "^0.3"), ("bytes", "^1")]),
+        ]);
+
+        let names = resolver.names_of_interest();
+        assert_eq!(names.len(), 2);
+        resolver.process(vec![
+            release("h2", "0.3.0", &[("bytes", "^1")]),
+            release("h2", "0.4.0", &[]),
+        ]);
+        resolver.process(vec![release("bytes", "1.0.0", &[])]);
+
+        assert!(resolver.names_of_interest().is_empty());
+
+        let tree = resolver.finalize();
+        assert_eq!(tree.dependencies.len(), 3);
+
+        let h2 = &tree.dependencies[1];
+        assert_eq!(h2.name.as_ref(), "h2");
+        assert_eq!(
+            h2.analyzed.latest_that_matches,
+            Some("0.3.0".parse().unwrap())
+        );
+        assert!(h2.analyzed.is_outdated());
+        assert_eq!(
+            h2.path,
+            vec!["reqwest".parse().unwrap(), "hyper".parse().unwrap()]
+        );
+        assert_eq!(tree.count_outdated(), 1);
+    }
+
+    #[test]
+    fn gives_up_on_crates_missing_from_the_index() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert(
+            "missing".parse().unwrap(),
+            CrateDep::External("^1".parse().unwrap()),
+        );
+
+        let mut resolver = DependencyTreeResolver::new(&"root".parse().unwrap(), &deps, None);
+        assert_eq!(resolver.names_of_interest().len(), 1);
+        assert!(resolver.names_of_interest().is_empty());
+        assert!(resolver.finalize().dependencies.is_empty());
+    }
+}
@@ -1,34 +1,53 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fmt,
     panic::RefUnwindSafe,
+    path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, Context as _, Error};
 use cadence::{MetricSink, NopMetricSink, StatsdClient};
-use crates_index::Index;
-use futures::{future::try_join_all, stream, StreamExt};
+use futures::{
+    future::{self, try_join_all},
+    stream, StreamExt,
+};
 use hyper::service::Service;
 use once_cell::sync::Lazy;
 use relative_path::{RelativePath, RelativePathBuf};
-use rustsec::database::Database;
+use rustsec::{advisory::Advisory, database::Database};
 use semver::VersionReq;
-use slog::Logger;
+use slog::{debug, warn, Logger};
 use stream::BoxStream;
 
-use crate::interactors::crates::{GetPopularCrates, QueryCrate};
+use crate::config::SiteTokens;
+use crate::interactors::crates::{GetPopularCrates, QueryCrate, QueryCrateResponse};
 use crate::interactors::github::GetPopularRepos;
-use crate::interactors::rustsec::FetchAdvisoryDatabase;
-use crate::interactors::RetrieveFileAtPath;
-use crate::models::crates::{AnalyzedDependencies, CrateName, CratePath, CrateRelease};
+use crate::interactors::{RetrieveDirectoriesAtPath, RetrieveFileAtPath};
+use crate::models::crates::{
+    AnalysisPolicy, AnalyzedDependencies, AnalyzedDependency, AnalyzedDependencyTree,
+    AnalyzedLockfile, CrateName, CratePath, CrateRelease, LockedPackage, RegistryName,
+};
 use crate::models::repo::{RepoPath, Repository};
-use crate::utils::cache::Cache;
+use crate::models::SubjectPath;
+use crate::parsers::lockfile::parse_lockfile;
+use crate::parsers::policy::parse_policy_toml;
+use crate::utils::advisory_db::AdvisoryDb;
+use crate::utils::cache::{Cache, RevalidatingCache};
+use crate::utils::history::{
+    AnalysisSummary, DependencySummary, History, HistoryEntry, MAX_ENTRIES_PER_SUBJECT,
+};
+use crate::utils::index::CrateIndex;
 
+mod failure;
 mod fut;
 mod machines;
 
-use self::fut::{analyze_dependencies, crawl_manifest};
+pub use self::failure::{AnalysisFailure, FailureKind, ManifestError};
+
+use self::fut::{analyze_dependencies, analyze_dependency_tree, analyze_lockfile, crawl_manifest};
+use self::machines::tree::RegistryCrate;
 
 #[derive(Clone, Debug)]
 pub struct Engine {
@@ -36,22 +55,79 @@ pub struct Engine {
     logger: Logger,
     metrics: StatsdClient,
     query_crate: Cache<QueryCrate, CrateName>,
+    query_registry_crate: Arc<HashMap<RegistryName, Cache<QueryCrate, CrateName>>>,
     get_popular_crates: Cache<GetPopularCrates, ()>,
     get_popular_repos: Cache<GetPopularRepos, ()>,
     retrieve_file_at_path: RetrieveFileAtPath,
-    fetch_advisory_db: Cache<FetchAdvisoryDatabase, ()>,
+    retrieve_directories_at_path: RetrieveDirectoriesAtPath,
+    advisory_db: AdvisoryDb,
+    history: History,
+    outcomes: RevalidatingCache<AnalysisSubject, Arc<AnalyzeDependenciesOutcome>, AnalysisFailure>,
+}
+
+/// What an outcome was computed for, keying the outcome cache
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum AnalysisSubject {
+    Repo(RepoPath),
+    Crate(CratePath),
+    CrateTree(CratePath),
+}
+
+impl AnalysisSubject {
+    /// Keys repositories case-insensitively, so that pushes reported with the canonical
+    /// spelling refresh the outcomes of every URL spelling
+    fn repo(repo_path: &RepoPath) -> AnalysisSubject {
+        AnalysisSubject::Repo(repo_path.to_lowercase())
+    }
+}
+
+/// Where the manifests and lockfile of an analyzed project are read from
+#[derive(Clone, Debug)]
+enum ManifestSource {
+    Repo(RepoPath),
+    /// Checkout on the local filesystem
+    Local(Arc<PathBuf>),
+}
+
+impl fmt::Display for ManifestSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestSource::Repo(repo_path) => repo_path.fmt(f),
+            ManifestSource::Local(root) => root.display().fmt(f),
+        }
+    }
 }
 
 impl Engine {
-    pub fn new(client: reqwest::Client, index: Index, logger: Logger) -> Engine {
+    pub fn new(
+        client: reqwest::Client,
+        index: CrateIndex,
+        registries: HashMap<RegistryName, CrateIndex>,
+        advisory_db: AdvisoryDb,
+        tokens: SiteTokens,
+        history: History,
+        logger: Logger,
+    ) -> Engine {
         let metrics = StatsdClient::from_sink("engine", NopMetricSink);
 
         let query_crate = Cache::new(
-            QueryCrate::new(index),
+            QueryCrate::new(index, None),
             Duration::from_secs(10),
             500,
             logger.clone(),
         );
+        let query_registry_crate = registries
+            .into_iter()
+            .map(|(registry, index)| {
+                let cache = Cache::new(
+                    QueryCrate::new(index, Some(registry.clone())),
+                    Duration::from_secs(10),
+                    500,
+                    logger.clone(),
+                );
+                (registry, cache)
+            })
+            .collect();
         let get_popular_crates = Cache::new(
             GetPopularCrates::new(client.clone()),
             Duration::from_secs(120),
@@ -64,11 +140,13 @@ impl Engine {
             1,
             logger.clone(),
         );
-        let retrieve_file_at_path = RetrieveFileAtPath::new(client.clone());
-        let fetch_advisory_db = Cache::new(
-            FetchAdvisoryDatabase::new(client.clone()),
-            Duration::from_secs(1800),
-            1,
+        let retrieve_file_at_path = RetrieveFileAtPath::new(client.clone(), tokens.clone());
+        let retrieve_directories_at_path = RetrieveDirectoriesAtPath::new(client.clone(), tokens);
+        let outcomes = RevalidatingCache::new(
+            Duration::from_secs(300),
+            Duration::from_secs(3600),
+            Duration::from_secs(60),
+            500,
             logger.clone(),
         );
 
@@ -77,10 +155,14 @@ impl Engine {
             logger,
             metrics,
             query_crate,
+            query_registry_crate: Arc::new(query_registry_crate),
             get_popular_crates,
             get_popular_repos,
             retrieve_file_at_path,
-            fetch_advisory_db,
+            retrieve_directories_at_path,
+            advisory_db,
+            history,
+            outcomes,
         }
     }
 
@@ -92,6 +174,10 @@ impl Engine {
 #[derive(Debug)]
 pub struct AnalyzeDependenciesOutcome {
     pub crates: Vec<(CrateName, AnalyzedDependencies)>,
+    /// Packages pinned by the repository's `Cargo.lock`, if it has one
+    pub lockfile: Option<AnalyzedLockfile>,
+    /// The full dependency graph, if it was requested
+    pub tree: Option<AnalyzedDependencyTree>,
     pub duration: Duration,
 }
 
@@ -101,11 +187,26 @@ impl AnalyzeDependenciesOutcome {
     }
 
     // TODO(feliix42): Why is this different from the any_outdated() function above?
-    /// Checks if any insecure main or build dependencies exist in the scanned crates
+    /// Checks if any insecure main or build dependencies exist in the scanned crates, or if the
+    /// lockfile or the dependency tree contain any insecure package
     pub fn any_insecure(&self) -> bool {
         self.crates
             .iter()
             .any(|&(_, ref deps)| deps.count_insecure() > 0)
+            || self.count_locked_insecure() > 0
+            || self.count_tree_insecure() > 0
+    }
+
+    /// Returns the number of insecure packages in the dependency tree
+    pub fn count_tree_insecure(&self) -> usize {
+        self.tree.as_ref().map_or(0, |tree| tree.count_insecure())
+    }
+
+    /// Returns the number of insecure packages pinned by the lockfile
+    pub fn count_locked_insecure(&self) -> usize {
+        self.lockfile
+            .as_ref()
+            .map_or(0, |lockfile| lockfile.count_insecure())
     }
 
     /// Checks if any dev-dependencies in the scanned crates are either outdated or insecure
@@ -139,6 +240,155 @@ impl AnalyzeDependenciesOutcome {
                 (outdated + deps.count_outdated(), total + deps.count_total())
             })
     }
+
+    /// Returns the number of outdated and the number of total dependencies, counting
+    /// dev-dependencies as well
+    pub fn outdated_ratio_with_dev(&self) -> (usize, usize) {
+        let (outdated, total) = self.outdated_ratio();
+        let dev_total: usize = self
+            .crates
+            .iter()
+            .flat_map(|(_, deps)| deps.dev.values())
+            .flat_map(AnalyzedDependency::declarations)
+            .count();
+        (outdated + self.count_dev_outdated(), total + dev_total)
+    }
+
+    /// Returns the number of distinct advisories that make the outcome insecure, i.e. that
+    /// affect main or build dependencies, the lockfile or the dependency tree
+    pub fn count_advisories(&self) -> usize {
+        self.collect_vulnerabilities(false).len()
+    }
+
+    /// Returns the advisories affecting any analyzed dependency or package, ordered by ID
+    pub fn vulnerabilities(&self) -> Vec<&Advisory> {
+        self.collect_vulnerabilities(true)
+    }
+
+    fn collect_vulnerabilities(&self, include_dev: bool) -> Vec<&Advisory> {
+        let mut vulnerabilities = Vec::new();
+        for (_, deps) in &self.crates {
+            vulnerabilities.extend(
+                deps.main
+                    .values()
+                    .chain(deps.build.values())
+                    .flat_map(AnalyzedDependency::declarations)
+                    .flat_map(|dep| &dep.vulnerabilities),
+            );
+            if include_dev {
+                vulnerabilities.extend(
+                    deps.dev
+                        .values()
+                        .flat_map(AnalyzedDependency::declarations)
+                        .flat_map(|dep| &dep.vulnerabilities),
+                );
+            }
+        }
+        if let Some(ref tree) = self.tree {
+            vulnerabilities.extend(
+                tree.dependencies
+                    .iter()
+                    .flat_map(|dep| &dep.analyzed.vulnerabilities),
+            );
+        }
+        if let Some(ref lockfile) = self.lockfile {
+            vulnerabilities.extend(
+                lockfile
+                    .packages
+                    .iter()
+                    .flat_map(|pkg| &pkg.vulnerabilities),
+            );
+        }
+
+        vulnerabilities.sort_unstable_by_key(|v| v.id());
+        vulnerabilities.dedup();
+        vulnerabilities
+    }
+
+    /// Returns the advisories that the repository's policy ignores, ordered by ID
+    pub fn ignored_vulnerabilities(&self) -> Vec<&Advisory> {
+        let mut vulnerabilities: Vec<&Advisory> = self
+            .crates
+            .iter()
+            .flat_map(|(_, deps)| {
+                deps.main
+                    .values()
+                    .chain(deps.dev.values())
+                    .chain(deps.build.values())
+            })
+            .flat_map(AnalyzedDependency::declarations)
+            .flat_map(|dep| &dep.ignored_vulnerabilities)
+            .collect();
+        if let Some(ref lockfile) = self.lockfile {
+            vulnerabilities.extend(
+                lockfile
+                    .packages
+                    .iter()
+                    .flat_map(|pkg| &pkg.ignored_vulnerabilities),
+            );
+        }
+
+        vulnerabilities.sort_unstable_by_key(|v| v.id());
+        vulnerabilities.dedup();
+        vulnerabilities
+    }
+
+    /// Checks if the repository's policy left out dependencies, kept pinned dependencies from
+    /// counting as outdated, or ignored advisories
+    pub fn any_ignored(&self) -> bool {
+        self.crates
+            .iter()
+            .any(|(_, deps)| !deps.ignored.is_empty() || deps.pinned_behind().next().is_some())
+            || self
+                .lockfile
+                .as_ref()
+                .is_some_and(|lockfile| !lockfile.ignored.is_empty())
+            || !self.ignored_vulnerabilities().is_empty()
+    }
+
+    /// Condenses the outcome into what is kept in the analysis history
+    fn summary(&self) -> AnalysisSummary {
+        let (outdated, total) = self.outdated_ratio();
+        let insecure = self
+            .crates
+            .iter()
+            .map(|(_, deps)| deps.count_insecure())
+            .sum::<usize>()
+            + self.count_locked_insecure();
+
+        let dependencies = self
+            .crates
+            .iter()
+            .flat_map(|(crate_name, deps)| {
+                let kinds = vec![
+                    ("normal", &deps.main),
+                    ("dev", &deps.dev),
+                    ("build", &deps.build),
+                ];
+                kinds.into_iter().flat_map(move |(kind, deps)| {
+                    let declarations = deps
+                        .iter()
+                        .flat_map(|(name, dep)| dep.declarations().map(move |dep| (name, dep)));
+                    declarations.map(move |(name, dep)| DependencySummary {
+                        crate_name: crate_name.as_ref().to_string(),
+                        kind,
+                        name: name.as_ref().to_string(),
+                        required: dep.required.to_string(),
+                        latest: dep.latest.as_ref().map(ToString::to_string),
+                        outdated: dep.is_outdated(),
+                        insecure: dep.is_insecure(),
+                    })
+                })
+            })
+            .collect();
+
+        AnalysisSummary {
+            outdated,
+            insecure,
+            total,
+            dependencies,
+        }
+    }
 }
 
 impl Engine {
@@ -159,29 +409,182 @@ impl Engine {
         Ok(crates)
     }
 
+    /// Analyzes the dependencies of a repository. Results are cached, and refreshed in the
+    /// background once they are a few minutes old.
     pub async fn analyze_repo_dependencies(
         &self,
         repo_path: RepoPath,
+    ) -> Result<Arc<AnalyzeDependenciesOutcome>, AnalysisFailure> {
+        let engine = self.clone();
+        self.outcomes
+            .get_or_compute(AnalysisSubject::repo(&repo_path), move || async move {
+                let outcome = engine
+                    .fresh_repo_dependencies(repo_path.clone())
+                    .await
+                    .map_err(AnalysisFailure::from)?;
+
+                engine
+                    .record_history(&SubjectPath::Repo(repo_path), &outcome)
+                    .await;
+                Ok(Arc::new(outcome))
+            })
+            .await
+    }
+
+    /// Drops the cached outcome of a repository and analyzes it again, so that the next
+    /// visit is served the state after a push
+    pub async fn reanalyze_repo_dependencies(
+        &self,
+        repo_path: RepoPath,
+    ) -> Result<(), AnalysisFailure> {
+        self.outcomes
+            .invalidate(&AnalysisSubject::repo(&repo_path))
+            .await;
+        self.analyze_repo_dependencies(repo_path).await?;
+        Ok(())
+    }
+
+    /// Analyzes a repository or, including its dependency graph, a crate release, bypassing
+    /// any cached outcome
+    pub async fn analyze_fresh(
+        &self,
+        subject_path: SubjectPath,
+    ) -> Result<Arc<AnalyzeDependenciesOutcome>, AnalysisFailure> {
+        match subject_path {
+            SubjectPath::Repo(repo_path) => {
+                self.outcomes
+                    .invalidate(&AnalysisSubject::repo(&repo_path))
+                    .await;
+                self.analyze_repo_dependencies(repo_path).await
+            }
+            SubjectPath::Crate(crate_path) => {
+                self.outcomes
+                    .invalidate(&AnalysisSubject::CrateTree(crate_path.clone()))
+                    .await;
+                self.analyze_crate_dependency_tree(crate_path).await
+            }
+        }
+    }
+
+    /// Returns the recorded analyses of a repository or crate, newest first
+    pub async fn history(&self, subject_path: &SubjectPath) -> Result<Vec<HistoryEntry>, Error> {
+        self.history
+            .load(subject_path, MAX_ENTRIES_PER_SUBJECT)
+            .await
+    }
+
+    async fn record_history(
+        &self,
+        subject_path: &SubjectPath,
+        outcome: &AnalyzeDependenciesOutcome,
+    ) {
+        if let Err(err) = self.history.record(subject_path, outcome.summary()).await {
+            warn!(self.logger, "failed to record analysis history: {}", err);
+        }
+    }
+
+    /// Analyzes the direct dependencies of a crate release, cached like repository analyses
+    pub async fn analyze_crate_dependencies(
+        &self,
+        crate_path: CratePath,
+    ) -> Result<Arc<AnalyzeDependenciesOutcome>, AnalysisFailure> {
+        let engine = self.clone();
+        self.outcomes
+            .get_or_compute(
+                AnalysisSubject::Crate(crate_path.clone()),
+                move || async move {
+                    let outcome = engine
+                        .fresh_crate_dependencies(crate_path.clone())
+                        .await
+                        .map_err(AnalysisFailure::from)?;
+
+                    engine
+                        .record_history(&SubjectPath::Crate(crate_path), &outcome)
+                        .await;
+                    Ok(Arc::new(outcome))
+                },
+            )
+            .await
+    }
+
+    /// Analyzes the direct dependencies of a crate release along with its full dependency graph
+    pub async fn analyze_crate_dependency_tree(
+        &self,
+        crate_path: CratePath,
+    ) -> Result<Arc<AnalyzeDependenciesOutcome>, AnalysisFailure> {
+        let engine = self.clone();
+        self.outcomes
+            .get_or_compute(
+                AnalysisSubject::CrateTree(crate_path.clone()),
+                move || async move {
+                    engine
+                        .fresh_crate_dependency_tree(crate_path)
+                        .await
+                        .map(Arc::new)
+                        .map_err(AnalysisFailure::from)
+                },
+            )
+            .await
+    }
+
+    /// Analyzes the dependencies of a local checkout, without caching the outcome or
+    /// recording it in the history
+    pub async fn analyze_local_dependencies(
+        &self,
+        root: PathBuf,
+    ) -> Result<AnalyzeDependenciesOutcome, AnalysisFailure> {
+        self.fresh_source_dependencies(ManifestSource::Local(Arc::new(root)))
+            .await
+            .map_err(AnalysisFailure::from)
+    }
+
+    async fn fresh_repo_dependencies(
+        &self,
+        repo_path: RepoPath,
+    ) -> Result<AnalyzeDependenciesOutcome, Error> {
+        self.fresh_source_dependencies(ManifestSource::Repo(repo_path))
+            .await
+    }
+
+    async fn fresh_source_dependencies(
+        &self,
+        source: ManifestSource,
     ) -> Result<AnalyzeDependenciesOutcome, Error> {
         let start = Instant::now();
 
         let entry_point = RelativePath::new("/").to_relative_path_buf();
         let engine = self.clone();
 
-        let manifest_output = crawl_manifest(self.clone(), repo_path.clone(), entry_point).await?;
+        let manifest_output =
+            crawl_manifest(self.clone(), source.clone(), entry_point.clone()).await?;
+
+        // a `.deps.toml` takes precedence over the metadata of the root manifest
+        let policy = match self.retrieve_policy_at_path(&source, &entry_point).await? {
+            Some(policy) => policy,
+            None => manifest_output.policy.unwrap_or_default(),
+        };
 
         let engine_for_analyze = engine.clone();
         let futures = manifest_output
             .crates
             .into_iter()
             .map(|(crate_name, deps)| async {
-                let analyzed_deps = analyze_dependencies(engine_for_analyze.clone(), deps).await?;
+                let analyzed_deps =
+                    analyze_dependencies(engine_for_analyze.clone(), deps, &policy).await?;
                 Ok::<_, Error>((crate_name, analyzed_deps))
             })
             .collect::<Vec<_>>();
 
         let crates = try_join_all(futures).await?;
 
+        let lockfile = match self
+            .retrieve_lockfile_at_path(&source, &entry_point)
+            .await?
+        {
+            Some(packages) => Some(analyze_lockfile(engine.clone(), packages, &policy).await?),
+            None => None,
+        };
+
         let duration = start.elapsed();
         // engine
         //     .metrics
@@ -191,42 +594,78 @@ impl Engine {
         //     .with_tag("repo_name", repo_path.name.as_ref())
         //     .send()?;
 
-        Ok(AnalyzeDependenciesOutcome { crates, duration })
+        Ok(AnalyzeDependenciesOutcome {
+            crates,
+            lockfile,
+            tree: None,
+            duration,
+        })
     }
 
-    pub async fn analyze_crate_dependencies(
+    async fn fresh_crate_dependencies(
         &self,
         crate_path: CratePath,
     ) -> Result<AnalyzeDependenciesOutcome, Error> {
         let start = Instant::now();
 
-        let query_response = self
-            .query_crate
-            .cached_query(crate_path.name.clone())
-            .await?;
+        let release = self.find_crate_release(&crate_path).await?;
+        let analyzed_deps =
+            analyze_dependencies(self.clone(), release.deps, &AnalysisPolicy::default()).await?;
 
-        let engine = self.clone();
+        let crates = vec![(crate_path.name, analyzed_deps)];
+        let duration = start.elapsed();
 
-        match query_response
-            .releases
-            .iter()
-            .find(|release| release.version == crate_path.version)
-        {
-            None => Err(anyhow!(
-                "could not find crate release with version {}",
-                crate_path.version
-            )),
+        Ok(AnalyzeDependenciesOutcome {
+            crates,
+            lockfile: None,
+            tree: None,
+            duration,
+        })
+    }
 
-            Some(release) => {
-                let analyzed_deps =
-                    analyze_dependencies(engine.clone(), release.deps.clone()).await?;
+    async fn fresh_crate_dependency_tree(
+        &self,
+        crate_path: CratePath,
+    ) -> Result<AnalyzeDependenciesOutcome, Error> {
+        let start = Instant::now();
 
-                let crates = vec![(crate_path.name, analyzed_deps)];
-                let duration = start.elapsed();
+        let release = self.find_crate_release(&crate_path).await?;
+        let policy = AnalysisPolicy::default();
 
-                Ok(AnalyzeDependenciesOutcome { crates, duration })
-            }
-        }
+        let (analyzed_deps, tree) = future::try_join(
+            analyze_dependencies(self.clone(), release.deps.clone(), &policy),
+            analyze_dependency_tree(self.clone(), release.name, release.deps),
+        )
+        .await?;
+
+        let crates = vec![(crate_path.name, analyzed_deps)];
+        let duration = start.elapsed();
+
+        Ok(AnalyzeDependenciesOutcome {
+            crates,
+            lockfile: None,
+            tree: Some(tree),
+            duration,
+        })
+    }
+
+    async fn find_crate_release(&self, crate_path: &CratePath) -> Result<CrateRelease, Error> {
+        let query_response = self
+            .query_crate(crate_path.registry.as_ref(), crate_path.name.clone())
+            .await
+            .map_err(AnalysisFailure::from)?;
+
+        query_response
+            .releases
+            .into_iter()
+            .find(|release| release.version == crate_path.version)
+            .ok_or_else(|| {
+                let err = anyhow!(
+                    "could not find crate release with version {}",
+                    crate_path.version
+                );
+                AnalysisFailure::new(FailureKind::NotFound, err).into()
+            })
     }
 
     pub async fn find_latest_crate_release(
@@ -234,7 +673,7 @@ impl Engine {
         name: CrateName,
         req: VersionReq,
     ) -> Result<Option<CrateRelease>, Error> {
-        let query_response = self.query_crate.cached_query(name).await?;
+        let query_response = self.query_crate(None, name).await?;
 
         let latest = query_response
             .releases
@@ -246,9 +685,31 @@ impl Engine {
         Ok(latest)
     }
 
+    /// Checks if crates can be looked up in a registry, which is always the case for crates.io
+    fn knows_registry(&self, registry: Option<&RegistryName>) -> bool {
+        registry.is_none_or(|registry| self.query_registry_crate.contains_key(registry))
+    }
+
+    /// Looks up a crate in crates.io or in one of the configured alternate registries
+    async fn query_crate(
+        &self,
+        registry: Option<&RegistryName>,
+        name: CrateName,
+    ) -> Result<QueryCrateResponse, Error> {
+        let cache = match registry {
+            Some(registry) => self.query_registry_crate.get(registry).ok_or_else(|| {
+                let err = anyhow!("unknown registry '{}'", registry.as_ref());
+                Error::from(AnalysisFailure::new(FailureKind::NotFound, err))
+            })?,
+            None => &self.query_crate,
+        };
+
+        cache.cached_query(name).await
+    }
+
     fn fetch_releases<'a, I>(&'a self, names: I) -> BoxStream<'a, anyhow::Result<Vec<CrateRelease>>>
     where
-        I: IntoIterator<Item = CrateName>,
+        I: IntoIterator<Item = RegistryCrate>,
         <I as IntoIterator>::IntoIter: Send + 'a,
     {
         let engine = self.clone();
@@ -263,24 +724,122 @@ impl Engine {
 
     async fn retrieve_manifest_at_path(
         &self,
-        repo_path: &RepoPath,
+        source: &ManifestSource,
         path: &RelativePathBuf,
     ) -> Result<String, Error> {
         let manifest_path = path.join(RelativePath::new("Cargo.toml"));
+        self.retrieve_file_at_path(source, manifest_path).await
+    }
+
+    /// Retrieves and parses the `Cargo.lock` next to the manifest at `path`, if there is one.
+    /// Failing to retrieve an existing lockfile fails the analysis.
+    async fn retrieve_lockfile_at_path(
+        &self,
+        source: &ManifestSource,
+        path: &RelativePathBuf,
+    ) -> Result<Option<Vec<LockedPackage>>, Error> {
+        let lockfile_path = path.join(RelativePath::new("Cargo.lock"));
+
+        let raw_lockfile = match self.retrieve_file_at_path(source, lockfile_path).await {
+            Ok(raw_lockfile) => raw_lockfile,
+            Err(err) if FailureKind::classify(&err) == FailureKind::NotFound => {
+                debug!(self.logger, "no lockfile found"; "repo" => source.to_string(), "error" => err.to_string());
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+
+        match parse_lockfile(&raw_lockfile) {
+            Ok(packages) => Ok(Some(packages)),
+            Err(err) => {
+                warn!(self.logger, "failed to parse lockfile"; "repo" => source.to_string(), "error" => err.to_string());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Retrieves and parses the `.deps.toml` in the directory at `path`, if there is one
+    async fn retrieve_policy_at_path(
+        &self,
+        source: &ManifestSource,
+        path: &RelativePathBuf,
+    ) -> Result<Option<AnalysisPolicy>, Error> {
+        let policy_path = path.join(RelativePath::new(".deps.toml"));
 
-        let mut service = self.retrieve_file_at_path.clone();
-        Ok(service.call((repo_path.clone(), manifest_path)).await?)
+        let raw_policy = match self
+            .retrieve_file_at_path(source, policy_path.clone())
+            .await
+        {
+            Ok(raw_policy) => raw_policy,
+            Err(err) if FailureKind::classify(&err) == FailureKind::NotFound => {
+                debug!(self.logger, "no policy found"; "repo" => source.to_string(), "error" => err.to_string());
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+
+        // broken policies are reported like broken manifests, rather than silently ignored
+        let policy = parse_policy_toml(&raw_policy).context(ManifestError { path: policy_path })?;
+        Ok(Some(policy))
+    }
+
+    async fn retrieve_file_at_path(
+        &self,
+        source: &ManifestSource,
+        path: RelativePathBuf,
+    ) -> Result<String, Error> {
+        match source {
+            ManifestSource::Repo(repo_path) => {
+                let mut service = self.retrieve_file_at_path.clone();
+                service.call((repo_path.clone(), path)).await
+            }
+            ManifestSource::Local(root) => {
+                let file_path = path.to_logical_path(root.as_path());
+                tokio::fs::read_to_string(&file_path)
+                    .await
+                    .with_context(|| format!("failed to read {}", file_path.display()))
+            }
+        }
+    }
+
+    async fn retrieve_directories_at_path(
+        &self,
+        source: &ManifestSource,
+        path: &RelativePathBuf,
+    ) -> Result<Vec<String>, Error> {
+        match source {
+            ManifestSource::Repo(repo_path) => {
+                let mut service = self.retrieve_directories_at_path.clone();
+                service.call((repo_path.clone(), path.clone())).await
+            }
+            ManifestSource::Local(root) => {
+                let dir_path = path.to_logical_path(root.as_path());
+                let mut entries = tokio::fs::read_dir(&dir_path)
+                    .await
+                    .with_context(|| format!("failed to read {}", dir_path.display()))?;
+
+                let mut directories = Vec::new();
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_type().await?.is_dir() {
+                        directories.push(entry.file_name().to_string_lossy().into_owned());
+                    }
+                }
+                Ok(directories)
+            }
+        }
     }
 
-    async fn fetch_advisory_db(&self) -> Result<Arc<Database>, Error> {
-        Ok(self.fetch_advisory_db.cached_query(()).await?)
+    fn advisory_db(&self) -> Result<Arc<Database>, Error> {
+        self.advisory_db
+            .get()
+            .ok_or_else(|| anyhow!("advisory database has not been loaded yet"))
     }
 }
 
 async fn resolve_crate_with_engine(
-    (crate_name, engine): (CrateName, Engine),
+    ((registry, crate_name), engine): (RegistryCrate, Engine),
 ) -> anyhow::Result<Vec<CrateRelease>> {
-    let crate_res = engine.query_crate.cached_query(crate_name).await?;
+    let crate_res = engine.query_crate(registry.as_ref(), crate_name).await?;
     Ok(crate_res.releases)
 }
 
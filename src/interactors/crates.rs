@@ -1,20 +1,32 @@
-use std::{fmt, str, task::Context, task::Poll};
+use std::{collections::HashMap, error, fmt, str, task::Context, task::Poll};
 
-use anyhow::{anyhow, Error};
-use crates_index::{Crate, DependencyKind, Index};
+use anyhow::Error;
+use crates_index::{Crate, DependencyKind};
+use derive_more::Display;
 use futures::FutureExt as _;
 use hyper::service::Service;
 use semver::{Version, VersionReq};
 use serde::Deserialize;
 use tokio::task::spawn_blocking;
 
+use super::error_for_status;
 use crate::{
-    models::crates::{CrateDep, CrateDeps, CrateName, CratePath, CrateRelease},
+    models::crates::{
+        CrateDep, CrateDeps, CrateName, CratePath, CrateRelease, DepKind, RegistryName,
+    },
+    utils::index::CrateIndex,
     BoxFuture,
 };
 
 const CRATES_API_BASE_URI: &str = "https://crates.io/api/v1";
 
+/// Error of a lookup for a crate that the index doesn't know
+#[derive(Debug, Display)]
+#[display(fmt = "crate '{}' not found", "_0.as_ref()")]
+pub struct CrateNotFound(pub CrateName);
+
+impl error::Error for CrateNotFound {}
+
 #[derive(Deserialize, Debug)]
 struct RegistryPackageDep {
     name: String,
@@ -34,7 +46,14 @@ struct RegistryPackage {
     yanked: bool,
 }
 
-fn convert_pkgs(krate: Crate) -> Result<QueryCrateResponse, Error> {
+/// Converts the index entry of a crate. Index entries don't record which registry the
+/// dependencies of alternate registry crates come from, so they are looked up in the same
+/// registry first and fall back to crates.io.
+fn convert_pkgs(
+    krate: Crate,
+    registry: Option<&RegistryName>,
+    mut in_registry: impl FnMut(&str) -> bool,
+) -> Result<QueryCrateResponse, Error> {
     let name: CrateName = krate.name().parse()?;
 
     let releases = krate
@@ -43,14 +62,25 @@ fn convert_pkgs(krate: Crate) -> Result<QueryCrateResponse, Error> {
         .map(|package| {
             let mut deps = CrateDeps::default();
             for dep in package.dependencies() {
-                let name = dep.crate_name().parse()?;
+                let name: CrateName = dep.crate_name().parse()?;
                 let req = VersionReq::parse(dep.requirement())?;
 
-                match dep.kind() {
-                    DependencyKind::Normal => deps.main.insert(name, CrateDep::External(req)),
-                    DependencyKind::Dev => deps.dev.insert(name, CrateDep::External(req)),
-                    _ => None,
+                let kind = match dep.kind() {
+                    DependencyKind::Normal => DepKind::Normal,
+                    DependencyKind::Dev => DepKind::Dev,
+                    DependencyKind::Build => DepKind::Build,
+                };
+                let features = dep.features().iter().cloned().collect();
+                let target = dep.target();
+                let is_optional = dep.is_optional();
+                let dep = match registry {
+                    Some(registry) if in_registry(name.as_ref()) => {
+                        CrateDep::Registry(registry.clone(), req)
+                    }
+                    _ => CrateDep::External(req),
                 };
+                // entries list a dependency once per platform, in no particular order
+                deps.insert(kind, name, dep, target, is_optional, features);
             }
             let version = Version::parse(package.version())?;
             Ok(CrateRelease {
@@ -58,6 +88,7 @@ fn convert_pkgs(krate: Crate) -> Result<QueryCrateResponse, Error> {
                 version,
                 deps,
                 yanked: package.is_yanked(),
+                registry: registry.cloned(),
             })
         })
         .collect::<Result<_, Error>>()?;
@@ -72,21 +103,34 @@ pub struct QueryCrateResponse {
 
 #[derive(Clone)]
 pub struct QueryCrate {
-    index: Index,
+    index: CrateIndex,
+    registry: Option<RegistryName>,
 }
 
 impl QueryCrate {
-    pub fn new(index: Index) -> Self {
-        Self { index }
+    pub fn new(index: CrateIndex, registry: Option<RegistryName>) -> Self {
+        Self { index, registry }
     }
 
-    pub async fn query(index: Index, crate_name: CrateName) -> anyhow::Result<QueryCrateResponse> {
-        let crate_name2 = crate_name.clone();
-        let krate = spawn_blocking(move || index.crate_(crate_name2.as_ref()))
-            .await?
-            .ok_or_else(|| anyhow!("crate '{}' not found", crate_name.as_ref()))?;
+    pub async fn query(
+        index: CrateIndex,
+        registry: Option<RegistryName>,
+        crate_name: CrateName,
+    ) -> anyhow::Result<QueryCrateResponse> {
+        spawn_blocking(move || {
+            index.with_lookup(|lookup| {
+                let krate =
+                    lookup(crate_name.as_ref()).ok_or_else(|| CrateNotFound(crate_name.clone()))?;
 
-        convert_pkgs(krate)
+                let mut known = HashMap::new();
+                convert_pkgs(krate, registry.as_ref(), |name| {
+                    *known
+                        .entry(name.to_string())
+                        .or_insert_with(|| lookup(name).is_some())
+                })
+            })
+        })
+        .await?
     }
 }
 
@@ -107,7 +151,8 @@ impl Service<CrateName> for QueryCrate {
 
     fn call(&mut self, crate_name: CrateName) -> Self::Future {
         let index = self.index.clone();
-        Self::query(index, crate_name).boxed()
+        let registry = self.registry.clone();
+        Self::query(index, registry, crate_name).boxed()
     }
 }
 
@@ -131,6 +176,7 @@ fn convert_summary(response: SummaryResponse) -> Result<Vec<CratePath>, Error> {
             Ok(CratePath {
                 name,
                 version: detail.max_version,
+                registry: None,
             })
         })
         .collect()
@@ -148,7 +194,7 @@ impl GetPopularCrates {
 
     pub async fn query(client: reqwest::Client) -> anyhow::Result<Vec<CratePath>> {
         let url = format!("{}/summary", CRATES_API_BASE_URI);
-        let res = client.get(&url).send().await?.error_for_status()?;
+        let res = error_for_status(client.get(&url).send().await?)?;
 
         let summary: SummaryResponse = res.json().await?;
         convert_summary(summary)
@@ -174,3 +220,49 @@ impl Service<()> for GetPopularCrates {
         Self::query(client).boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_dependencies_declared_for_several_platforms() {
+        let deps = r#"[
+            {"name": "libc", "req": "^0.2.80", "features": [], "optional": true, "default_features": true, "target": "cfg(unix)", "kind": "normal"},
+            {"name": "libc", "req": "^0.2", "features": [], "optional": false, "default_features": true, "target": null, "kind": "normal"},
+            {"name": "winapi", "req": "^0.3", "features": ["winuser"], "optional": false, "default_features": true, "target": "cfg(windows)", "kind": "normal"},
+            {"name": "winapi", "req": "^0.3", "features": [], "optional": false, "default_features": true, "target": "wasm32-unknown-unknown", "kind": "normal"},
+            {"name": "winapi", "req": "^0.2", "features": [], "optional": false, "default_features": true, "target": "x86_64-unknown-redox", "kind": "normal"},
+            {"name": "libc", "req": "^0.2", "features": [], "optional": false, "default_features": true, "target": null, "kind": "dev"}
+        ]"#;
+        let entry = format!(
+            r#"{{"name": "platform", "vers": "1.0.0", "deps": {}, "cksum": "{}", "features": {{}}, "yanked": false}}"#,
+            deps.replace('\n', ""),
+            "0".repeat(64)
+        );
+        let krate = Crate::from_slice(entry.as_bytes()).unwrap();
+
+        let response = convert_pkgs(krate, None, |_| false).unwrap();
+        let deps = &response.releases[0].deps;
+
+        assert_eq!(deps.main.len(), 2);
+        assert_eq!(
+            deps.main["libc"],
+            CrateDep::External("^0.2".parse().unwrap())
+        );
+        assert_eq!(deps.targets.main.get("libc"), None);
+        assert!(!deps.optional.main.contains("libc"));
+        assert_eq!(deps.platforms.main["libc"][0].target, "cfg(unix)");
+        assert!(deps.platforms.main["libc"][0].optional);
+        assert_eq!(
+            deps.targets.main["winapi"],
+            "cfg(windows), wasm32-unknown-unknown"
+        );
+        assert!(deps.features.main["winapi"].contains("winuser"));
+        assert_eq!(
+            deps.platforms.main["winapi"][0].dep,
+            CrateDep::External("^0.2".parse().unwrap())
+        );
+        assert_eq!(deps.dev.len(), 1);
+    }
+}
@@ -9,12 +9,13 @@ use futures::FutureExt as _;
 use hyper::service::Service;
 use serde::Deserialize;
 
+use super::error_for_status;
 use crate::{
     models::repo::{RepoPath, Repository},
     BoxFuture,
 };
 
-const GITHUB_API_BASE_URI: &str = "https://api.github.com";
+pub(crate) const GITHUB_API_BASE_URI: &str = "https://api.github.com";
 
 #[derive(Deserialize)]
 struct GithubSearchResponse {
@@ -49,7 +50,7 @@ impl GetPopularRepos {
             GITHUB_API_BASE_URI
         );
 
-        let res = client.get(&url).send().await?.error_for_status()?;
+        let res = error_for_status(client.get(&url).send().await?)?;
         let summary: GithubSearchResponse = res.json().await?;
 
         summary
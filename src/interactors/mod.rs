@@ -1,40 +1,213 @@
 use std::{
-    fmt,
+    error, fmt,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use anyhow::{anyhow, Error};
+use derive_more::Display;
 use futures::FutureExt as _;
 use hyper::service::Service;
 use relative_path::RelativePathBuf;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, LINK, RETRY_AFTER,
+};
+use reqwest::redirect::{Action, Attempt, Policy as RedirectPolicy};
+use reqwest::StatusCode;
+use reqwest::Url;
+use serde::Deserialize;
 
-use crate::{models::repo::RepoPath, BoxFuture};
+use crate::{
+    config::{AccessToken, SiteTokens},
+    models::repo::{CustomSite, CustomSiteKind, RepoPath, RepoSite},
+    BoxFuture, DEPS_RS_UA,
+};
 
 pub mod crates;
 pub mod github;
-pub mod rustsec;
 
+/// Context of errors caused by an exhausted rate limit
+#[derive(Debug, Display)]
+#[display(fmt = "rate limit exceeded")]
+pub struct RateLimitExceeded;
+
+impl error::Error for RateLimitExceeded {}
+
+/// Turns error statuses into errors. Sites like GitHub answer with 403 both when access is
+/// denied and once the rate limit is exhausted, which only the headers tell apart.
+pub(crate) fn error_for_status(res: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let headers = res.headers();
+    let rate_limited = match res.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            headers.contains_key(RETRY_AFTER)
+                || headers
+                    .get("x-ratelimit-remaining")
+                    .is_some_and(|remaining| remaining == "0")
+        }
+        _ => false,
+    };
+
+    match res.error_for_status() {
+        Err(err) if rate_limited => Err(Error::from(err).context(RateLimitExceeded)),
+        res => Ok(res?),
+    }
+}
+
+/// Checks if a redirect stays on the same scheme, host and port
+fn is_same_origin(from: &Url, to: &Url) -> bool {
+    from.scheme() == to.scheme()
+        && from.host_str() == to.host_str()
+        && from.port_or_known_default() == to.port_or_known_default()
+}
+
+/// Follows redirects within the origin of the first request only. Access tokens in headers
+/// other than `Authorization`, like GitLab's `private-token`, are kept on cross-origin
+/// redirects, so they would leak to the redirect target.
+fn same_origin_redirects(attempt: Attempt<'_>) -> Action {
+    let previous = attempt.previous();
+    if previous.len() > 5 {
+        attempt.error("too many redirects")
+    } else if is_same_origin(&previous[0], attempt.url()) {
+        attempt.follow()
+    } else {
+        attempt.stop()
+    }
+}
+
+/// HTTP clients for the sites that repositories are hosted on
 #[derive(Clone)]
-pub struct RetrieveFileAtPath {
+struct SiteClient {
     client: reqwest::Client,
+    /// Used for requests carrying an access token
+    authenticated: reqwest::Client,
+    tokens: SiteTokens,
+}
+
+impl SiteClient {
+    fn new(client: reqwest::Client, tokens: SiteTokens) -> SiteClient {
+        let authenticated = reqwest::Client::builder()
+            .user_agent(DEPS_RS_UA)
+            .redirect(RedirectPolicy::custom(same_origin_redirects))
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("failed to build HTTP client");
+
+        SiteClient {
+            client,
+            authenticated,
+            tokens,
+        }
+    }
+
+    fn is_authenticated(&self, site: &RepoSite) -> bool {
+        self.tokens.get(site).is_some()
+    }
+
+    /// Starts a request to a site, authenticated with its access token if there is one
+    fn get(&self, site: &RepoSite, url: &str) -> anyhow::Result<reqwest::RequestBuilder> {
+        match self.tokens.get(site) {
+            Some(token) => authorize(self.authenticated.get(url), site, token),
+            None => Ok(self.client.get(url)),
+        }
+    }
+}
+
+/// Attaches the site's access token in the header the site expects.
+fn authorize(
+    req: reqwest::RequestBuilder,
+    site: &RepoSite,
+    token: &AccessToken,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    let token = token.secret();
+
+    let (name, value) = match site {
+        RepoSite::Gitlab
+        | RepoSite::Custom(CustomSite {
+            kind: CustomSiteKind::Gitlab,
+            ..
+        }) => (HeaderName::from_static("private-token"), token.to_string()),
+        RepoSite::Bitbucket => (AUTHORIZATION, format!("Bearer {}", token)),
+        RepoSite::Github | RepoSite::Custom(_) => (AUTHORIZATION, format!("token {}", token)),
+    };
+
+    let mut value = HeaderValue::from_str(&value)
+        .map_err(|_| anyhow!("invalid access token for {}", site.as_ref()))?;
+    value.set_sensitive(true);
+
+    Ok(req.header(name, value))
+}
+
+#[derive(Clone)]
+pub struct RetrieveFileAtPath {
+    client: SiteClient,
 }
 
 impl RetrieveFileAtPath {
-    pub fn new(client: reqwest::Client) -> Self {
-        Self { client }
+    pub fn new(client: reqwest::Client, tokens: SiteTokens) -> Self {
+        Self {
+            client: SiteClient::new(client, tokens),
+        }
     }
 
-    pub async fn query(
-        client: reqwest::Client,
+    /// Returns the URL to fetch a file from. Raw file URLs of some sites don't accept access
+    /// tokens, so authenticated requests go through their APIs instead.
+    fn file_url(repo_path: &RepoPath, path: &RelativePathBuf, authenticated: bool) -> String {
+        let qual = repo_path.qual.as_ref();
+        let name = repo_path.name.as_ref();
+        let git_ref = repo_path.git_ref.as_ref().map_or("HEAD", |r| r.as_ref());
+
+        match repo_path.site {
+            RepoSite::Gitlab
+            | RepoSite::Custom(CustomSite {
+                kind: CustomSiteKind::Gitlab,
+                ..
+            }) if authenticated => format!(
+                "{}/api/v4/projects/{}%2F{}/repository/files/{}/raw?ref={}",
+                repo_path.site.to_base_uri(),
+                qual,
+                name,
+                path.normalize().as_str().replace('/', "%2F"),
+                git_ref
+            ),
+            RepoSite::Bitbucket if authenticated => format!(
+                "https://api.bitbucket.org/2.0/repositories/{}/{}/src/{}/{}",
+                qual,
+                name,
+                git_ref,
+                path.normalize()
+            ),
+            RepoSite::Custom(CustomSite {
+                kind: CustomSiteKind::GithubEnterprise,
+                ..
+            }) if authenticated => format!(
+                "{}/api/v3/repos/{}/{}/contents/{}?ref={}",
+                repo_path.site.to_base_uri(),
+                qual,
+                name,
+                path.normalize(),
+                git_ref
+            ),
+            _ => repo_path.to_usercontent_file_url(path),
+        }
+    }
+
+    async fn query(
+        client: SiteClient,
         repo_path: RepoPath,
         path: RelativePathBuf,
     ) -> anyhow::Result<String> {
-        let url = repo_path.to_usercontent_file_url(&path);
-        let res = client.get(&url).send().await?;
-
-        if !res.status().is_success() {
-            return Err(anyhow!("Status code {} for URI {}", res.status(), url));
+        let url = Self::file_url(&repo_path, &path, client.is_authenticated(&repo_path.site));
+        let mut req = client.get(&repo_path.site, &url)?;
+        if let RepoSite::Custom(CustomSite {
+            kind: CustomSiteKind::GithubEnterprise,
+            ..
+        }) = repo_path.site
+        {
+            // the contents API returns JSON unless asked for the raw file
+            req = req.header(ACCEPT, "application/vnd.github.raw");
         }
+        let res = error_for_status(req.send().await?)?;
 
         Ok(res.text().await?)
     }
@@ -50,8 +223,7 @@ impl Service<(RepoPath, RelativePathBuf)> for RetrieveFileAtPath {
     }
 
     fn call(&mut self, (repo_path, path): (RepoPath, RelativePathBuf)) -> Self::Future {
-        let client = self.client.clone();
-        Self::query(client, repo_path, path).boxed()
+        Self::query(self.client.clone(), repo_path, path).boxed()
     }
 }
 
@@ -60,3 +232,396 @@ impl fmt::Debug for RetrieveFileAtPath {
         f.write_str("RetrieveFileAtPath")
     }
 }
+
+#[derive(Deserialize)]
+struct GithubContentsEntry {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabTreeEntry {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct BitbucketSrcResponse {
+    values: Vec<BitbucketSrcEntry>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketSrcEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// Listings spanning more pages than this are rejected rather than silently truncated
+const MAX_LISTING_PAGES: usize = 10;
+
+/// Finds the URL of the next page in a `Link` header, as sent by GitHub, GitLab and Gitea
+fn next_page_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"");
+        is_next.then(|| {
+            url.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+    })
+}
+
+/// Lists the names of the subdirectories at a path of a repository.
+#[derive(Clone)]
+pub struct RetrieveDirectoriesAtPath {
+    client: SiteClient,
+}
+
+impl RetrieveDirectoriesAtPath {
+    pub fn new(client: reqwest::Client, tokens: SiteTokens) -> Self {
+        Self {
+            client: SiteClient::new(client, tokens),
+        }
+    }
+
+    fn listing_url(repo_path: &RepoPath, path: &RelativePathBuf) -> String {
+        let path = path.normalize();
+        let qual = repo_path.qual.as_ref();
+        let name = repo_path.name.as_ref();
+        let git_ref = repo_path.git_ref.as_ref().map(|r| r.as_ref());
+
+        match repo_path.site {
+            RepoSite::Github => {
+                let url = format!(
+                    "{}/repos/{}/{}/contents/{}",
+                    github::GITHUB_API_BASE_URI,
+                    qual,
+                    name,
+                    path
+                );
+                match git_ref {
+                    Some(git_ref) => format!("{}?ref={}", url, git_ref),
+                    None => url,
+                }
+            }
+            RepoSite::Gitlab => {
+                let url = format!(
+                    "https://gitlab.com/api/v4/projects/{}%2F{}/repository/tree?per_page=100&path={}",
+                    qual, name, path
+                );
+                match git_ref {
+                    Some(git_ref) => format!("{}&ref={}", url, git_ref),
+                    None => url,
+                }
+            }
+            RepoSite::Bitbucket => {
+                // directory paths end with a slash, except for the root
+                let dir = if path.as_str().is_empty() {
+                    String::new()
+                } else {
+                    format!("{}/", path)
+                };
+                format!(
+                    "https://api.bitbucket.org/2.0/repositories/{}/{}/src/{}/{}?pagelen=100",
+                    qual,
+                    name,
+                    git_ref.unwrap_or("HEAD"),
+                    dir
+                )
+            }
+            RepoSite::Custom(site) => {
+                let base = repo_path.site.to_base_uri();
+                let url = match site.kind {
+                    CustomSiteKind::Gitlab => format!(
+                        "{}/api/v4/projects/{}%2F{}/repository/tree?per_page=100&path={}",
+                        base, qual, name, path
+                    ),
+                    CustomSiteKind::Gitea => format!(
+                        "{}/api/v1/repos/{}/{}/contents/{}?limit=100",
+                        base, qual, name, path
+                    ),
+                    CustomSiteKind::GithubEnterprise => format!(
+                        "{}/api/v3/repos/{}/{}/contents/{}?per_page=100",
+                        base, qual, name, path
+                    ),
+                };
+                match git_ref {
+                    Some(git_ref) => format!("{}&ref={}", url, git_ref),
+                    None => url,
+                }
+            }
+        }
+    }
+
+    async fn query(
+        client: SiteClient,
+        repo_path: RepoPath,
+        path: RelativePathBuf,
+    ) -> anyhow::Result<Vec<String>> {
+        let first_url = Url::parse(&Self::listing_url(&repo_path, &path))?;
+        let mut next_url = Some(first_url.clone());
+        let mut directories = Vec::new();
+
+        for _ in 0..MAX_LISTING_PAGES {
+            let url = match next_url.take() {
+                Some(url) => url,
+                None => return Ok(directories),
+            };
+            let (page, next) = Self::query_page(&client, &repo_path, url.as_str()).await?;
+            directories.extend(page);
+
+            if let Some(next) = next {
+                let next = url.join(&next)?;
+                // the access token must not be sent anywhere else
+                if !is_same_origin(&first_url, &next) {
+                    return Err(anyhow!(
+                        "next page of listing is on another origin: {}",
+                        next
+                    ));
+                }
+                next_url = Some(next);
+            }
+        }
+
+        match next_url {
+            Some(_) => Err(anyhow!(
+                "directory listing at '{}' spans more than {} pages",
+                path,
+                MAX_LISTING_PAGES
+            )),
+            None => Ok(directories),
+        }
+    }
+
+    /// Retrieves a page of a directory listing, along with the URL of the next page
+    async fn query_page(
+        client: &SiteClient,
+        repo_path: &RepoPath,
+        url: &str,
+    ) -> anyhow::Result<(Vec<String>, Option<String>)> {
+        let res = error_for_status(client.get(&repo_path.site, url)?.send().await?)?;
+        let next = next_page_link(res.headers());
+
+        let page = match repo_path.site {
+            RepoSite::Github
+            | RepoSite::Custom(CustomSite {
+                kind: CustomSiteKind::Gitea | CustomSiteKind::GithubEnterprise,
+                ..
+            }) => {
+                let directories = res
+                    .json::<Vec<GithubContentsEntry>>()
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.kind == "dir")
+                    .map(|entry| entry.name)
+                    .collect();
+                (directories, next)
+            }
+            RepoSite::Gitlab
+            | RepoSite::Custom(CustomSite {
+                kind: CustomSiteKind::Gitlab,
+                ..
+            }) => {
+                let directories = res
+                    .json::<Vec<GitlabTreeEntry>>()
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.kind == "tree")
+                    .map(|entry| entry.name)
+                    .collect();
+                (directories, next)
+            }
+            RepoSite::Bitbucket => {
+                let response = res.json::<BitbucketSrcResponse>().await?;
+                let directories = response
+                    .values
+                    .into_iter()
+                    .filter(|entry| entry.kind == "commit_directory")
+                    .filter_map(|entry| entry.path.rsplit('/').next().map(str::to_string))
+                    .collect();
+                (directories, response.next)
+            }
+        };
+
+        Ok(page)
+    }
+}
+
+impl Service<(RepoPath, RelativePathBuf)> for RetrieveDirectoriesAtPath {
+    type Response = Vec<String>;
+    type Error = Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, (repo_path, path): (RepoPath, RelativePathBuf)) -> Self::Future {
+        Self::query(self.client.clone(), repo_path, path).boxed()
+    }
+}
+
+impl fmt::Debug for RetrieveDirectoriesAtPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetrieveDirectoriesAtPath")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use hyper::header::LOCATION;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+
+    use super::*;
+    use crate::config::Config;
+
+    /// Serves every request on a local port with the given handler
+    fn serve<F>(handler: F) -> SocketAddr
+    where
+        F: Fn(Request<Body>) -> Response<Body> + Clone + Send + Sync + 'static,
+    {
+        let make_service = make_service_fn(move |_| {
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let res = handler(req);
+                    async move { Ok::<_, Infallible>(res) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn redirect_to(location: String) -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::FOUND)
+            .header(LOCATION, location)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn keeps_tokens_from_other_origins() {
+        let leaked = Arc::new(AtomicBool::new(false));
+        let target = {
+            let leaked = leaked.clone();
+            serve(move |req| {
+                if req.headers().contains_key("private-token") {
+                    leaked.store(true, Ordering::SeqCst);
+                }
+                Response::new(Body::from("manifest"))
+            })
+        };
+        let origin = serve(move |req| match req.uri().path() {
+            "/moved" => redirect_to("/Cargo.toml".to_string()),
+            "/Cargo.toml" => Response::new(Body::from("manifest")),
+            _ => redirect_to(format!("http://{}/Cargo.toml", target)),
+        });
+
+        let config: Config = toml::from_str("[tokens]\ngitlab = \"secret\"").unwrap();
+        let client = SiteClient::new(reqwest::Client::new(), config.site_tokens());
+
+        // redirects within the origin are still followed
+        let res = client
+            .get(&RepoSite::Gitlab, &format!("http://{}/moved", origin))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client
+            .get(&RepoSite::Gitlab, &format!("http://{}/elsewhere", origin))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert!(!leaked.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn tells_rate_limits_apart_from_denied_access() {
+        let addr = serve(|req| {
+            let res = Response::builder().status(StatusCode::FORBIDDEN);
+            let res = match req.uri().path() {
+                "/limited" => res.header("x-ratelimit-remaining", "0"),
+                _ => res.header("x-ratelimit-remaining", "42"),
+            };
+            res.body(Body::empty()).unwrap()
+        });
+
+        let client = reqwest::Client::new();
+        for (path, rate_limited) in [("/limited", true), ("/denied", false)] {
+            let res = client
+                .get(format!("http://{}{}", addr, path))
+                .send()
+                .await
+                .unwrap();
+            let err = error_for_status(res).unwrap_err();
+            assert_eq!(err.is::<RateLimitExceeded>(), rate_limited);
+        }
+    }
+
+    #[test]
+    fn lists_bitbucket_root_without_double_slash() {
+        let repo_path = RepoPath::from_parts("bitbucket", "owner", "repo").unwrap();
+
+        let url = RetrieveDirectoriesAtPath::listing_url(&repo_path, &"".into());
+        assert_eq!(
+            url,
+            "https://api.bitbucket.org/2.0/repositories/owner/repo/src/HEAD/?pagelen=100"
+        );
+
+        let url = RetrieveDirectoriesAtPath::listing_url(&repo_path, &"crates".into());
+        assert_eq!(
+            url,
+            "https://api.bitbucket.org/2.0/repositories/owner/repo/src/HEAD/crates/?pagelen=100"
+        );
+    }
+
+    #[test]
+    fn finds_next_page_links() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                "<https://gitlab.com/api/v4/tree?page=2>; rel=\"next\", \
+                 <https://gitlab.com/api/v4/tree?page=5>; rel=\"last\"",
+            ),
+        );
+        assert_eq!(
+            next_page_link(&headers).as_deref(),
+            Some("https://gitlab.com/api/v4/tree?page=2")
+        );
+
+        headers.insert(
+            LINK,
+            HeaderValue::from_static("<https://gitlab.com/api/v4/tree?page=1>; rel=\"first\""),
+        );
+        assert_eq!(next_page_link(&headers), None);
+    }
+}
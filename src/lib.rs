@@ -0,0 +1,18 @@
+#![deny(rust_2018_idioms)]
+#![warn(missing_debug_implementations)]
+
+use std::{future::Future, pin::Pin};
+
+pub mod config;
+pub mod engine;
+mod interactors;
+pub mod models;
+pub mod notifier;
+mod parsers;
+pub mod server;
+pub mod utils;
+
+/// Future crate's BoxFuture without the explicit lifetime parameter.
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+pub const DEPS_RS_UA: &str = "deps.rs";
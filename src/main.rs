@@ -2,10 +2,9 @@
 #![warn(missing_debug_implementations)]
 
 use std::{
+    collections::HashMap,
     env,
-    future::Future,
     net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
-    pin::Pin,
     time::Duration,
 };
 
@@ -19,21 +18,16 @@ use hyper::{
 use reqwest::redirect::Policy as RedirectPolicy;
 use slog::{error, info, o, Drain, Logger};
 
-mod engine;
-mod interactors;
-mod models;
-mod parsers;
-mod server;
-mod utils;
-
-use self::engine::Engine;
-use self::server::App;
-use self::utils::index::ManagedIndex;
-
-/// Future crate's BoxFuture without the explicit lifetime parameter.
-pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
-
-const DEPS_RS_UA: &str = "deps.rs";
+use shiny_robots::config::Config;
+use shiny_robots::engine::Engine;
+use shiny_robots::models::crates::RegistryName;
+use shiny_robots::models::repo::CustomSite;
+use shiny_robots::notifier::{Notifier, Subscription};
+use shiny_robots::server::App;
+use shiny_robots::utils::advisory_db::ManagedAdvisoryDb;
+use shiny_robots::utils::history::History;
+use shiny_robots::utils::index::ManagedIndex;
+use shiny_robots::DEPS_RS_UA;
 
 fn init_metrics() -> QueuingMetricSink {
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -71,6 +65,17 @@ async fn main() {
 
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
 
+    let config = Config::load().expect("could not read configuration");
+    let tokens = config.site_tokens();
+    let webhook_secrets = config.webhook_secrets();
+    CustomSite::register(config.sites).expect("invalid custom site configuration");
+    let subscriptions = config
+        .subscriptions
+        .into_iter()
+        .map(Subscription::from_config)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("invalid subscription configuration");
+
     let mut managed_index = ManagedIndex::new(Duration::from_secs(20), logger.clone());
     if let Err(e) = managed_index.initial_clone().await {
         error!(
@@ -84,16 +89,77 @@ async fn main() {
         managed_index.refresh_at_interval().await;
     });
 
-    let mut engine = Engine::new(client.clone(), index, logger.new(o!()));
+    let mut registries = HashMap::new();
+    for registry in &config.registries {
+        let name: RegistryName = registry.name.parse().expect("invalid registry name");
+        let mut managed_index = ManagedIndex::for_registry(
+            &registry.name,
+            &registry.index,
+            Duration::from_secs(60),
+            logger.clone(),
+        )
+        .expect("invalid registry index url");
+        if let Err(e) = managed_index.initial_clone().await {
+            error!(
+                logger,
+                "failed running initial clone of the {} registry index: {}", registry.name, e
+            );
+        }
+
+        registries.insert(name, managed_index.index());
+        tokio::spawn(async move {
+            managed_index.refresh_at_interval().await;
+        });
+    }
+
+    let mut managed_advisory_db = ManagedAdvisoryDb::new(Duration::from_secs(1800), logger.clone());
+    if let Err(e) = managed_advisory_db.initial_clone().await {
+        error!(logger, "failed loading the advisory-db: {}", e);
+    }
+
+    let advisory_db = managed_advisory_db.db();
+    tokio::spawn(async move {
+        managed_advisory_db.refresh_at_interval().await;
+    });
+
+    let history = match config.history_db {
+        Some(ref path) => History::open(path).expect("could not open the history database"),
+        None => History::in_memory().expect("could not create the history database"),
+    };
+
+    let mut engine = Engine::new(
+        client.clone(),
+        index,
+        registries,
+        advisory_db.clone(),
+        tokens,
+        history,
+        logger.new(o!()),
+    );
     engine.set_metrics(metrics);
 
+    if !subscriptions.is_empty() {
+        let notifier = Notifier::new(
+            engine.clone(),
+            advisory_db,
+            subscriptions,
+            config.smtp.as_ref(),
+            client.clone(),
+            Duration::from_secs(60),
+            logger.new(o!()),
+        )
+        .expect("invalid notification configuration");
+        tokio::spawn(notifier.run());
+    }
+
     let svc_logger = logger.new(o!());
     let make_svc = make_service_fn(move |_socket: &AddrStream| {
         let engine = engine.clone();
         let logger = svc_logger.clone();
+        let webhook_secrets = webhook_secrets.clone();
 
         async move {
-            let server = App::new(logger.clone(), engine.clone());
+            let server = App::new(logger.clone(), engine.clone(), webhook_secrets);
             Ok::<_, hyper::Error>(service_fn(move |req| {
                 let server = server.clone();
                 async move { server.handle(req).await }
@@ -1,15 +1,17 @@
-use std::{borrow::Borrow, str::FromStr};
+use std::{borrow::Borrow, fmt, str::FromStr};
 
 use anyhow::{anyhow, Error};
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use relative_path::RelativePathBuf;
 use rustsec::Advisory;
 use semver::{Version, VersionReq};
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CratePath {
     pub name: CrateName,
     pub version: Version,
+    /// Alternate registry the crate is published to, `None` meaning crates.io
+    pub registry: Option<RegistryName>,
 }
 
 impl CratePath {
@@ -17,8 +19,40 @@ impl CratePath {
         Ok(CratePath {
             name: name.parse()?,
             version: version.parse()?,
+            registry: None,
         })
     }
+
+    pub fn with_registry(self, registry: Option<RegistryName>) -> CratePath {
+        CratePath { registry, ..self }
+    }
+}
+
+/// Name of an alternate registry, as used in the `registry` key of a dependency
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RegistryName(String);
+
+impl AsRef<str> for RegistryName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl FromStr for RegistryName {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<RegistryName, Error> {
+        let is_valid = !input.is_empty()
+            && input
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+        if !is_valid {
+            Err(anyhow!("failed to validate registry name: {}", input))
+        } else {
+            Ok(RegistryName(input.to_string()))
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -64,17 +98,69 @@ pub struct CrateRelease {
     pub version: Version,
     pub deps: CrateDeps,
     pub yanked: bool,
+    /// Alternate registry the release was published to, `None` meaning crates.io
+    pub registry: Option<RegistryName>,
+}
+
+/// A crates.io package pinned by a `Cargo.lock`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub name: CrateName,
+    pub version: Version,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CrateDep {
     External(VersionReq),
+    /// Published to an alternate registry rather than crates.io
+    Registry(RegistryName, VersionReq),
     Internal(RelativePathBuf),
+    /// Declared with `workspace = true`, to be resolved against the workspace's dependencies
+    Inherited,
+    Git(GitDep),
+}
+
+/// A dependency pulled straight from a git repository
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitDep {
+    pub url: String,
+    pub pin: GitPin,
 }
 
-impl CrateDep {
-    pub fn is_external(&self) -> bool {
-        matches!(self, CrateDep::External(_))
+impl GitDep {
+    /// Returns a link to the repository that is safe to embed, if the URL points to one.
+    /// `ssh` and `git` URLs are linked to the web page of the same host and path.
+    pub fn web_url(&self) -> Option<String> {
+        let url = reqwest::Url::parse(&self.url).ok()?;
+        match url.scheme() {
+            "http" | "https" => Some(url.into()),
+            "ssh" | "git" | "git+ssh" => Some(format!(
+                "https://{}{}",
+                url.host_str()?,
+                url.path().trim_end_matches(".git")
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The commit a git dependency is pinned to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GitPin {
+    Rev(String),
+    Branch(String),
+    Tag(String),
+    DefaultBranch,
+}
+
+impl fmt::Display for GitPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitPin::Rev(rev) => write!(f, "rev = {}", rev),
+            GitPin::Branch(branch) => write!(f, "branch = {}", branch),
+            GitPin::Tag(tag) => write!(f, "tag = {}", tag),
+            GitPin::DefaultBranch => f.write_str("default branch"),
+        }
     }
 }
 
@@ -83,162 +169,585 @@ pub struct CrateDeps {
     pub main: IndexMap<CrateName, CrateDep>,
     pub dev: IndexMap<CrateName, CrateDep>,
     pub build: IndexMap<CrateName, CrateDep>,
+    pub targets: CrateDepTargets,
+    pub optional: CrateDepOptional,
+    pub features: CrateDepFeatures,
+    pub platforms: CrateDepPlatforms,
+}
+
+/// Platforms (`cfg(..)` expressions or target triples) of the dependencies that are only
+/// declared in `[target.*]` tables
+#[derive(Clone, Debug, Default)]
+pub struct CrateDepTargets {
+    pub main: IndexMap<CrateName, String>,
+    pub dev: IndexMap<CrateName, String>,
+    pub build: IndexMap<CrateName, String>,
+}
+
+/// Dependencies that are only enabled through features
+#[derive(Clone, Debug, Default)]
+pub struct CrateDepOptional {
+    pub main: IndexSet<CrateName>,
+    pub dev: IndexSet<CrateName>,
+    pub build: IndexSet<CrateName>,
+}
+
+/// Features enabled on the dependencies that declare any
+#[derive(Clone, Debug, Default)]
+pub struct CrateDepFeatures {
+    pub main: IndexMap<CrateName, IndexSet<String>>,
+    pub dev: IndexMap<CrateName, IndexSet<String>>,
+    pub build: IndexMap<CrateName, IndexSet<String>>,
+}
+
+/// A `[target.*]` declaration of a dependency that requires something else than its main
+/// declaration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlatformDep {
+    pub target: String,
+    pub dep: CrateDep,
+    pub optional: bool,
+    pub features: IndexSet<String>,
+}
+
+/// Other declarations of dependencies for specific platforms, in addition to those in
+/// `main`, `dev` and `build`
+#[derive(Clone, Debug, Default)]
+pub struct CrateDepPlatforms {
+    pub main: IndexMap<CrateName, Vec<PlatformDep>>,
+    pub dev: IndexMap<CrateName, Vec<PlatformDep>>,
+    pub build: IndexMap<CrateName, Vec<PlatformDep>>,
+}
+
+/// The table a dependency is declared in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl CrateDeps {
+    /// Adds a dependency declared for the given platform, or for all platforms if `target` is
+    /// `None`.
+    ///
+    /// Unconditional declarations take precedence, and declarations with the same requirement
+    /// for several platforms are listed once with all their platforms. Platforms requiring
+    /// something else are kept in `platforms`.
+    pub fn insert(
+        &mut self,
+        kind: DepKind,
+        name: CrateName,
+        dep: CrateDep,
+        target: Option<&str>,
+        is_optional: bool,
+        dep_features: IndexSet<String>,
+    ) {
+        let (deps, targets, optional, features, platforms) = match kind {
+            DepKind::Normal => (
+                &mut self.main,
+                &mut self.targets.main,
+                &mut self.optional.main,
+                &mut self.features.main,
+                &mut self.platforms.main,
+            ),
+            DepKind::Dev => (
+                &mut self.dev,
+                &mut self.targets.dev,
+                &mut self.optional.dev,
+                &mut self.features.dev,
+                &mut self.platforms.dev,
+            ),
+            DepKind::Build => (
+                &mut self.build,
+                &mut self.targets.build,
+                &mut self.optional.build,
+                &mut self.features.build,
+                &mut self.platforms.build,
+            ),
+        };
+
+        match (deps.get(&name), target) {
+            (Some(existing), Some(target)) => {
+                if *existing == dep {
+                    // unconditional declarations already cover every platform
+                    if let Some(existing_target) = targets.get_mut(&name) {
+                        existing_target.push_str(", ");
+                        existing_target.push_str(target);
+                    }
+                    return;
+                }
+
+                let others = platforms.entry(name).or_default();
+                if let Some(other) = others.iter_mut().find(|other| other.dep == dep) {
+                    other.target.push_str(", ");
+                    other.target.push_str(target);
+                } else {
+                    others.push(PlatformDep {
+                        target: target.to_string(),
+                        dep,
+                        optional: is_optional,
+                        features: dep_features,
+                    });
+                }
+                return;
+            }
+            // a crate can't be declared twice in the same table
+            (Some(_), None) if !targets.contains_key(&name) => return,
+            _ => {}
+        }
+
+        // the first declaration, or an unconditional one replacing a platform-specific one
+        let previous_target = targets.shift_remove(&name);
+        let previous_optional = optional.shift_remove(&name);
+        let previous_features = features.shift_remove(&name).unwrap_or_default();
+        if let Some(target) = target {
+            targets.insert(name.clone(), target.to_string());
+        }
+        if is_optional {
+            optional.insert(name.clone());
+        }
+        if !dep_features.is_empty() {
+            features.insert(name.clone(), dep_features);
+        }
+        if let (Some(previous), Some(previous_target)) =
+            (deps.insert(name.clone(), dep), previous_target)
+        {
+            if deps.get(&name) != Some(&previous) {
+                platforms.entry(name).or_default().insert(
+                    0,
+                    PlatformDep {
+                        target: previous_target,
+                        dep: previous,
+                        optional: previous_optional,
+                        features: previous_features,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Maintainer preferences for analyzing a repository, read from its `.deps.toml` or the
+/// `[package.metadata.deps-rs]` table of its root manifest
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisPolicy {
+    /// Crates that are left out of the analysis
+    pub ignore: IndexSet<CrateName>,
+    /// IDs or aliases of advisories that don't make dependencies insecure
+    pub ignore_advisories: IndexSet<String>,
+    /// Whether dev-dependencies are left out of the analysis. Lockfiles don't tell them apart,
+    /// so locked packages are still checked.
+    pub exclude_dev: bool,
+    /// Crates that are held back on purpose, so they don't count as outdated
+    pub pinned: IndexSet<CrateName>,
+}
+
+impl AnalysisPolicy {
+    pub fn ignores_advisory(&self, advisory: &Advisory) -> bool {
+        self.ignore_advisories.contains(advisory.id().as_str())
+            || advisory
+                .metadata
+                .aliases
+                .iter()
+                .any(|alias| self.ignore_advisories.contains(alias.as_str()))
+    }
 }
 
 #[derive(Debug)]
 pub struct AnalyzedDependency {
     pub required: VersionReq,
+    pub target: Option<String>,
+    pub optional: bool,
+    pub features: IndexSet<String>,
+    pub registry: Option<RegistryName>,
     pub latest_that_matches: Option<Version>,
     pub latest: Option<Version>,
     pub vulnerabilities: Vec<Advisory>,
+    /// Advisories affecting the dependency that the repository's policy ignores
+    pub ignored_vulnerabilities: Vec<Advisory>,
+    /// Held back on purpose according to the repository's policy
+    pub pinned: bool,
+    /// Declarations for specific platforms that require something else
+    pub platforms: Vec<AnalyzedDependency>,
 }
 
 impl AnalyzedDependency {
     pub fn new(required: VersionReq) -> AnalyzedDependency {
         AnalyzedDependency {
             required,
+            target: None,
+            optional: false,
+            features: IndexSet::new(),
+            registry: None,
             latest_that_matches: None,
             latest: None,
             vulnerabilities: Vec::new(),
+            ignored_vulnerabilities: Vec::new(),
+            pinned: false,
+            platforms: Vec::new(),
         }
     }
 
+    /// Returns the dependency followed by its declarations for other platforms
+    pub fn declarations(&self) -> impl Iterator<Item = &AnalyzedDependency> {
+        std::iter::once(self).chain(&self.platforms)
+    }
+
     pub fn is_insecure(&self) -> bool {
         !self.vulnerabilities.is_empty()
     }
 
+    /// Checks if a newer release exists, unless the dependency is pinned on purpose
     pub fn is_outdated(&self) -> bool {
+        !self.pinned && self.is_behind()
+    }
+
+    /// Checks if a newer release exists that the requirement doesn't match
+    pub fn is_behind(&self) -> bool {
         self.latest > self.latest_that_matches
     }
 
     pub fn deps_rs_path(&self, name: &str) -> String {
-        match &self.latest_that_matches {
-            Some(version) => ["/crate/", name, "/", version.to_string().as_str()].concat(),
-            None => ["/crate/", name].concat(),
+        match (&self.registry, &self.latest_that_matches) {
+            (Some(registry), Some(version)) => [
+                "/crate/",
+                registry.as_ref(),
+                "/",
+                name,
+                "/",
+                version.to_string().as_str(),
+            ]
+            .concat(),
+            // there is no redirect to the latest release for alternate registries
+            (Some(_), None) => "#".to_string(),
+            (None, Some(version)) => ["/crate/", name, "/", version.to_string().as_str()].concat(),
+            (None, None) => ["/crate/", name].concat(),
         }
     }
 }
 
+/// A git dependency, along with the newest release of the same crate on crates.io
+#[derive(Debug)]
+pub struct AnalyzedGitDependency {
+    pub git: GitDep,
+    pub latest: Option<Version>,
+}
+
+impl AnalyzedGitDependency {
+    /// Checks if the crate is also published to crates.io, so the git dependency could be
+    /// replaced by a release
+    pub fn is_published(&self) -> bool {
+        self.latest.is_some()
+    }
+}
+
 #[derive(Debug)]
 pub struct AnalyzedDependencies {
     pub main: IndexMap<CrateName, AnalyzedDependency>,
     pub dev: IndexMap<CrateName, AnalyzedDependency>,
     pub build: IndexMap<CrateName, AnalyzedDependency>,
+    /// Git dependencies of all kinds, which don't count towards the status
+    pub git: IndexMap<CrateName, AnalyzedGitDependency>,
+    /// Dependencies left out of the analysis by the repository's policy
+    pub ignored: IndexSet<CrateName>,
 }
 
 impl AnalyzedDependencies {
     pub fn new(deps: &CrateDeps) -> AnalyzedDependencies {
-        let main = deps
-            .main
-            .iter()
-            .filter_map(|(name, dep)| {
-                if let CrateDep::External(ref req) = dep {
-                    Some((name.clone(), AnalyzedDependency::new(req.clone())))
-                } else {
-                    None
+        fn analyze_dep(
+            dep: &CrateDep,
+            target: Option<&String>,
+            optional: bool,
+            features: Option<&IndexSet<String>>,
+        ) -> Option<AnalyzedDependency> {
+            let mut analyzed = match dep {
+                CrateDep::External(req) => AnalyzedDependency::new(req.clone()),
+                CrateDep::Registry(registry, req) => {
+                    let mut analyzed = AnalyzedDependency::new(req.clone());
+                    analyzed.registry = Some(registry.clone());
+                    analyzed
                 }
-            })
-            .collect();
-        let dev = deps
-            .dev
-            .iter()
-            .filter_map(|(name, dep)| {
-                if let CrateDep::External(ref req) = dep {
-                    Some((name.clone(), AnalyzedDependency::new(req.clone())))
-                } else {
-                    None
-                }
-            })
-            .collect();
-        let build = deps
-            .build
+                _ => return None,
+            };
+            analyzed.target = target.cloned();
+            analyzed.optional = optional;
+            analyzed.features = features.cloned().unwrap_or_default();
+            Some(analyzed)
+        }
+
+        fn analyze(
+            deps: &IndexMap<CrateName, CrateDep>,
+            targets: &IndexMap<CrateName, String>,
+            optional: &IndexSet<CrateName>,
+            features: &IndexMap<CrateName, IndexSet<String>>,
+            platforms: &IndexMap<CrateName, Vec<PlatformDep>>,
+        ) -> IndexMap<CrateName, AnalyzedDependency> {
+            deps.iter()
+                .filter_map(|(name, dep)| {
+                    let main = analyze_dep(
+                        dep,
+                        targets.get(name),
+                        optional.contains(name),
+                        features.get(name),
+                    );
+                    let others = platforms.get(name).into_iter().flatten().map(|other| {
+                        analyze_dep(
+                            &other.dep,
+                            Some(&other.target),
+                            other.optional,
+                            Some(&other.features),
+                        )
+                    });
+
+                    let mut declarations = std::iter::once(main).chain(others).flatten();
+                    let mut analyzed = declarations.next()?;
+                    analyzed.platforms = declarations.collect();
+                    Some((name.clone(), analyzed))
+                })
+                .collect()
+        }
+
+        let git = deps
+            .main
             .iter()
-            .filter_map(|(name, dep)| {
-                if let CrateDep::External(ref req) = dep {
-                    Some((name.clone(), AnalyzedDependency::new(req.clone())))
-                } else {
-                    None
-                }
+            .chain(&deps.dev)
+            .chain(&deps.build)
+            .filter_map(|(name, dep)| match dep {
+                CrateDep::Git(git) => Some((
+                    name.clone(),
+                    AnalyzedGitDependency {
+                        git: git.clone(),
+                        latest: None,
+                    },
+                )),
+                _ => None,
             })
             .collect();
-        AnalyzedDependencies { main, dev, build }
+
+        AnalyzedDependencies {
+            main: analyze(
+                &deps.main,
+                &deps.targets.main,
+                &deps.optional.main,
+                &deps.features.main,
+                &deps.platforms.main,
+            ),
+            dev: analyze(
+                &deps.dev,
+                &deps.targets.dev,
+                &deps.optional.dev,
+                &deps.features.dev,
+                &deps.platforms.dev,
+            ),
+            build: analyze(
+                &deps.build,
+                &deps.targets.build,
+                &deps.optional.build,
+                &deps.features.build,
+                &deps.platforms.build,
+            ),
+            git,
+            ignored: IndexSet::new(),
+        }
     }
 
     /// Counts the total number of main and build dependencies
     pub fn count_total(&self) -> usize {
-        self.main.len() + self.build.len()
+        declarations(&self.main).count() + declarations(&self.build).count()
     }
 
     /// Returns the number of outdated main and build dependencies
     pub fn count_outdated(&self) -> usize {
-        let main_outdated = self
-            .main
-            .iter()
-            .filter(|&(_, dep)| dep.is_outdated())
+        let main_outdated = declarations(&self.main)
+            .filter(|dep| dep.is_outdated())
             .count();
-        let build_outdated = self
-            .build
-            .iter()
-            .filter(|&(_, dep)| dep.is_outdated())
+        let build_outdated = declarations(&self.build)
+            .filter(|dep| dep.is_outdated())
             .count();
         main_outdated + build_outdated
     }
 
     /// Returns the number of insecure main and build dependencies
     pub fn count_insecure(&self) -> usize {
-        let main_insecure = self
-            .main
-            .iter()
-            .filter(|&(_, dep)| dep.is_insecure())
+        let main_insecure = declarations(&self.main)
+            .filter(|dep| dep.is_insecure())
             .count();
-        let build_insecure = self
-            .build
-            .iter()
-            .filter(|&(_, dep)| dep.is_insecure())
+        let build_insecure = declarations(&self.build)
+            .filter(|dep| dep.is_insecure())
             .count();
         main_insecure + build_insecure
     }
 
     /// Checks if any outdated main or build dependencies exist
     pub fn any_outdated(&self) -> bool {
-        let main_any_outdated = self.main.iter().any(|(_, dep)| dep.is_outdated());
-        let build_any_outdated = self.build.iter().any(|(_, dep)| dep.is_outdated());
+        let main_any_outdated = declarations(&self.main).any(|dep| dep.is_outdated());
+        let build_any_outdated = declarations(&self.build).any(|dep| dep.is_outdated());
         main_any_outdated || build_any_outdated
     }
 
     /// Counts the number of outdated `dev-dependencies`
     pub fn count_dev_outdated(&self) -> usize {
-        self.dev
-            .iter()
-            .filter(|&(_, dep)| dep.is_outdated())
+        declarations(&self.dev)
+            .filter(|dep| dep.is_outdated())
             .count()
     }
 
     /// Counts the number of insecure `dev-dependencies`
     pub fn count_dev_insecure(&self) -> usize {
-        self.dev
-            .iter()
-            .filter(|&(_, dep)| dep.is_insecure())
+        declarations(&self.dev)
+            .filter(|dep| dep.is_insecure())
             .count()
     }
 
     /// Returns `true` if any dev-dependencies are either insecure or outdated.
     pub fn any_dev_issues(&self) -> bool {
-        self.dev
+        declarations(&self.dev).any(|dep| dep.is_outdated() || dep.is_insecure())
+    }
+
+    /// Returns the pinned dependencies of all kinds that newer releases exist for
+    pub fn pinned_behind(&self) -> impl Iterator<Item = (&CrateName, &AnalyzedDependency)> {
+        self.main
             .iter()
-            .any(|(_, dep)| dep.is_outdated() || dep.is_insecure())
+            .chain(&self.dev)
+            .chain(&self.build)
+            .flat_map(|(name, dep)| dep.declarations().map(move |dep| (name, dep)))
+            .filter(|(_, dep)| dep.pinned && dep.is_behind())
     }
 }
 
+/// Returns the dependencies of a kind along with their declarations for other platforms
+fn declarations(
+    deps: &IndexMap<CrateName, AnalyzedDependency>,
+) -> impl Iterator<Item = &AnalyzedDependency> {
+    deps.values().flat_map(AnalyzedDependency::declarations)
+}
+
+#[derive(Debug)]
+pub struct AnalyzedLockedPackage {
+    pub name: CrateName,
+    pub version: Version,
+    pub yanked: bool,
+    pub vulnerabilities: Vec<Advisory>,
+    /// Advisories affecting the package that the repository's policy ignores
+    pub ignored_vulnerabilities: Vec<Advisory>,
+}
+
+impl AnalyzedLockedPackage {
+    pub fn new(package: LockedPackage) -> AnalyzedLockedPackage {
+        AnalyzedLockedPackage {
+            name: package.name,
+            version: package.version,
+            yanked: false,
+            vulnerabilities: Vec::new(),
+            ignored_vulnerabilities: Vec::new(),
+        }
+    }
+
+    pub fn is_insecure(&self) -> bool {
+        !self.vulnerabilities.is_empty()
+    }
+}
+
+/// The packages pinned by a `Cargo.lock`, including transitive dependencies
+#[derive(Debug)]
+pub struct AnalyzedLockfile {
+    pub packages: Vec<AnalyzedLockedPackage>,
+    /// Locked packages left out of the analysis by the repository's policy
+    pub ignored: IndexSet<CrateName>,
+}
+
+impl AnalyzedLockfile {
+    /// Returns the number of locked packages affected by security advisories
+    pub fn count_insecure(&self) -> usize {
+        self.packages.iter().filter(|pkg| pkg.is_insecure()).count()
+    }
+
+    /// Returns the number of locked packages whose version has been yanked
+    pub fn count_yanked(&self) -> usize {
+        self.packages.iter().filter(|pkg| pkg.yanked).count()
+    }
+}
+
+/// A dependency found anywhere in the dependency graph of a crate
+#[derive(Debug)]
+pub struct TransitiveDependency {
+    /// Chain of crates from the analyzed crate down to the one that requires this dependency
+    pub path: Vec<CrateName>,
+    pub name: CrateName,
+    /// `latest_that_matches` holds the version the requirement resolves to
+    pub analyzed: AnalyzedDependency,
+}
+
+#[derive(Debug)]
+pub struct AnalyzedDependencyTree {
+    pub dependencies: Vec<TransitiveDependency>,
+}
+
+impl AnalyzedDependencyTree {
+    /// Returns the number of outdated dependencies in the tree
+    pub fn count_outdated(&self) -> usize {
+        self.dependencies
+            .iter()
+            .filter(|dep| dep.analyzed.is_outdated())
+            .count()
+    }
+
+    /// Returns the number of insecure dependencies in the tree
+    pub fn count_insecure(&self) -> usize {
+        self.dependencies
+            .iter()
+            .filter(|dep| dep.analyzed.is_insecure())
+            .count()
+    }
+}
+
+/// Dependencies declared in `[workspace.dependencies]`, keyed by the name members use to
+/// inherit them, which differs from the crate name for renamed dependencies
+pub type WorkspaceDeps = IndexMap<CrateName, WorkspaceDep>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkspaceDep {
+    pub name: CrateName,
+    pub dep: CrateDep,
+    /// Features that every member inheriting the dependency enables
+    pub features: IndexSet<String>,
+}
+
 #[derive(Clone, Debug)]
 pub enum CrateManifest {
     Package(CrateName, CrateDeps),
     Workspace {
         members: Vec<RelativePathBuf>,
+        exclude: Vec<RelativePathBuf>,
+        workspace_deps: WorkspaceDeps,
     },
     Mixed {
         name: CrateName,
         deps: CrateDeps,
         members: Vec<RelativePathBuf>,
+        exclude: Vec<RelativePathBuf>,
+        workspace_deps: WorkspaceDeps,
     },
 }
+
+/// RUSTSEC-2021-0020 against `hyper`, for tests that need an advisory
+#[cfg(test)]
+pub(crate) fn test_advisory() -> Advisory {
+    r#"```toml
+[advisory]
+id = "RUSTSEC-2021-0020"
+package = "hyper"
+date = "2021-02-05"
+
+[versions]
+patched = [">= 0.14.3", "^0.13.10"]
+```
+
+# Multiple Transfer-Encoding headers misinterprets request payload
+
+hyper's HTTP server code had a flaw that incorrectly understands some requests.
+"#
+    .parse()
+    .unwrap()
+}
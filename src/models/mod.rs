@@ -1,7 +1,31 @@
 pub mod crates;
 pub mod repo;
 
+#[derive(Clone, Debug)]
 pub enum SubjectPath {
     Repo(self::repo::RepoPath),
     Crate(self::crates::CratePath),
 }
+
+impl SubjectPath {
+    /// Path of the subject's status page, relative to the site root
+    pub fn path(&self) -> String {
+        match self {
+            SubjectPath::Repo(repo_path) => format!(
+                "repo/{}/{}/{}",
+                repo_path.site.as_ref(),
+                repo_path.qual.as_ref(),
+                repo_path.name.as_ref()
+            ),
+            SubjectPath::Crate(crate_path) => match crate_path.registry {
+                Some(ref registry) => format!(
+                    "crate/{}/{}/{}",
+                    registry.as_ref(),
+                    crate_path.name.as_ref(),
+                    crate_path.version
+                ),
+                None => format!("crate/{}/{}", crate_path.name.as_ref(), crate_path.version),
+            },
+        }
+    }
+}
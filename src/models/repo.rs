@@ -1,7 +1,9 @@
 use std::{fmt, str::FromStr};
 
-use anyhow::{anyhow, ensure, Error};
+use anyhow::{anyhow, bail, ensure, Error};
+use once_cell::sync::OnceCell;
 use relative_path::RelativePath;
+use serde::Deserialize;
 
 #[derive(Clone, Debug)]
 pub struct Repository {
@@ -9,11 +11,13 @@ pub struct Repository {
     pub description: String,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RepoPath {
     pub site: RepoSite,
     pub qual: RepoQualifier,
     pub name: RepoName,
+    /// Branch, tag or commit to analyze, `None` meaning the default branch
+    pub git_ref: Option<GitRef>,
 }
 
 impl RepoPath {
@@ -22,19 +26,62 @@ impl RepoPath {
             site: site.parse()?,
             qual: qual.parse()?,
             name: name.parse()?,
+            git_ref: None,
         })
     }
 
+    pub fn with_git_ref(self, git_ref: Option<GitRef>) -> RepoPath {
+        RepoPath { git_ref, ..self }
+    }
+
+    /// Lowercases the owner and name, which repository sites match case-insensitively,
+    /// unlike refs
+    pub fn to_lowercase(&self) -> RepoPath {
+        RepoPath {
+            qual: RepoQualifier(self.qual.0.to_lowercase()),
+            name: RepoName(self.name.0.to_lowercase()),
+            ..self.clone()
+        }
+    }
+
     pub fn to_usercontent_file_url(&self, path: &RelativePath) -> String {
+        let git_ref = self.git_ref.as_ref().map_or("HEAD", |r| r.as_ref());
+
+        if let RepoSite::Custom(site) = self.site {
+            return site
+                .raw_url
+                .replace("{qual}", self.qual.as_ref())
+                .replace("{name}", self.name.as_ref())
+                .replace("{ref}", git_ref)
+                .replace("{path}", path.normalize().as_str());
+        }
+
         format!(
             "{}/{}/{}/{}/{}",
             self.site.to_usercontent_base_uri(),
             self.qual.as_ref(),
             self.name.as_ref(),
-            self.site.to_usercontent_repo_suffix(),
+            self.site.to_usercontent_repo_suffix(git_ref),
             path.normalize()
         )
     }
+
+    /// Returns the URL of the repository's web page, pointing at the ref if one is set
+    pub fn to_web_url(&self) -> String {
+        let base = format!(
+            "{}/{}/{}",
+            self.site.to_base_uri(),
+            self.qual.as_ref(),
+            self.name.as_ref()
+        );
+
+        match self.git_ref {
+            Some(ref git_ref) => {
+                format!("{}/{}/{}", base, self.site.tree_segment(), git_ref.as_ref())
+            }
+            None => base,
+        }
+    }
 }
 
 impl fmt::Display for RepoPath {
@@ -45,15 +92,22 @@ impl fmt::Display for RepoPath {
             self.site.as_ref(),
             self.qual.as_ref(),
             self.name.as_ref()
-        )
+        )?;
+
+        if let Some(ref git_ref) = self.git_ref {
+            write!(f, "@{}", git_ref.as_ref())?;
+        }
+
+        Ok(())
     }
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum RepoSite {
     Github,
     Gitlab,
     Bitbucket,
+    Custom(&'static CustomSite),
 }
 
 impl RepoSite {
@@ -62,6 +116,7 @@ impl RepoSite {
             RepoSite::Github => "https://github.com",
             RepoSite::Gitlab => "https://gitlab.com",
             RepoSite::Bitbucket => "https://bitbucket.org",
+            RepoSite::Custom(site) => site.base_url.trim_end_matches('/'),
         }
     }
 
@@ -70,13 +125,29 @@ impl RepoSite {
             RepoSite::Github => "https://raw.githubusercontent.com",
             RepoSite::Gitlab => "https://gitlab.com",
             RepoSite::Bitbucket => "https://bitbucket.org",
+            RepoSite::Custom(site) => site.base_url.trim_end_matches('/'),
+        }
+    }
+
+    pub fn to_usercontent_repo_suffix(&self, git_ref: &str) -> String {
+        match self {
+            RepoSite::Github => git_ref.to_string(),
+            RepoSite::Gitlab | RepoSite::Bitbucket | RepoSite::Custom(_) => {
+                format!("raw/{}", git_ref)
+            }
         }
     }
 
-    pub fn to_usercontent_repo_suffix(&self) -> &'static str {
+    pub fn tree_segment(&self) -> &'static str {
         match self {
-            RepoSite::Github => "HEAD",
-            RepoSite::Gitlab | RepoSite::Bitbucket => "raw/HEAD",
+            RepoSite::Github => "tree",
+            RepoSite::Gitlab => "-/tree",
+            RepoSite::Bitbucket => "src",
+            RepoSite::Custom(site) => match site.kind {
+                CustomSiteKind::Gitlab => "-/tree",
+                CustomSiteKind::Gitea => "src",
+                CustomSiteKind::GithubEnterprise => "tree",
+            },
         }
     }
 }
@@ -89,7 +160,11 @@ impl FromStr for RepoSite {
             "github" => Ok(RepoSite::Github),
             "gitlab" => Ok(RepoSite::Gitlab),
             "bitbucket" => Ok(RepoSite::Bitbucket),
-            _ => Err(anyhow!("unknown repo site identifier")),
+            _ => CUSTOM_SITES
+                .get()
+                .and_then(|sites| sites.iter().find(|site| site.name == input))
+                .map(RepoSite::Custom)
+                .ok_or_else(|| anyhow!("unknown repo site identifier")),
         }
     }
 }
@@ -100,11 +175,64 @@ impl AsRef<str> for RepoSite {
             RepoSite::Github => "github",
             RepoSite::Gitlab => "gitlab",
             RepoSite::Bitbucket => "bitbucket",
+            RepoSite::Custom(site) => &site.name,
+        }
+    }
+}
+
+static CUSTOM_SITES: OnceCell<Vec<CustomSite>> = OnceCell::new();
+
+/// A self-hosted code forge, configured at startup
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub struct CustomSite {
+    /// Identifier used in `/repo/:site/...` URLs
+    pub name: String,
+    pub kind: CustomSiteKind,
+    /// Web root of the instance, e.g. `https://gitlab.example.com`
+    pub base_url: String,
+    /// Raw file URL with `{qual}`, `{name}`, `{ref}` and `{path}` placeholders
+    pub raw_url: String,
+}
+
+impl CustomSite {
+    /// Makes the given sites available to `RepoSite::from_str`. Can only be called once.
+    pub fn register(sites: Vec<CustomSite>) -> Result<(), Error> {
+        for (idx, site) in sites.iter().enumerate() {
+            let is_valid = !site.name.is_empty()
+                && site
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+            ensure!(is_valid, "invalid custom site name {:?}", site.name);
+
+            if matches!(site.name.as_str(), "github" | "gitlab" | "bitbucket")
+                || sites[..idx].iter().any(|other| other.name == site.name)
+            {
+                bail!("duplicate site name {:?}", site.name);
+            }
+
+            ensure!(
+                site.raw_url.contains("{path}"),
+                "raw_url of site {:?} is missing the {{path}} placeholder",
+                site.name
+            );
         }
+
+        CUSTOM_SITES
+            .set(sites)
+            .map_err(|_| anyhow!("custom sites are already registered"))
     }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CustomSiteKind {
+    Gitlab,
+    Gitea,
+    GithubEnterprise,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RepoQualifier(String);
 
 impl FromStr for RepoQualifier {
@@ -126,7 +254,7 @@ impl AsRef<str> for RepoQualifier {
     }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RepoName(String);
 
 impl FromStr for RepoName {
@@ -148,6 +276,33 @@ impl AsRef<str> for RepoName {
     }
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GitRef(String);
+
+impl FromStr for GitRef {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<GitRef, Error> {
+        let is_valid = !input.is_empty()
+            && input
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' || c == '/')
+            && !input.starts_with('/')
+            && !input.ends_with('/')
+            && !input.contains("..")
+            && !input.contains("//");
+
+        ensure!(is_valid, "invalid git ref");
+        Ok(GitRef(input.to_string()))
+    }
+}
+
+impl AsRef<str> for GitRef {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,4 +348,78 @@ mod tests {
             assert_eq!(out.to_string(), exp);
         }
     }
+
+    #[test]
+    fn correct_raw_url_generation_with_git_ref() {
+        let git_ref = Some("release/1.x".parse().unwrap());
+
+        let repo = RepoPath::from_parts("github", "deps-rs", "deps.rs")
+            .unwrap()
+            .with_git_ref(git_ref.clone());
+        let out = repo.to_usercontent_file_url(RelativePath::new("Cargo.toml"));
+        assert_eq!(
+            out,
+            "https://raw.githubusercontent.com/deps-rs/deps.rs/release/1.x/Cargo.toml"
+        );
+
+        let repo = RepoPath::from_parts("gitlab", "deps-rs", "deps.rs")
+            .unwrap()
+            .with_git_ref(git_ref);
+        let out = repo.to_usercontent_file_url(RelativePath::new("Cargo.toml"));
+        assert_eq!(
+            out,
+            "https://gitlab.com/deps-rs/deps.rs/raw/release/1.x/Cargo.toml"
+        );
+    }
+
+    #[test]
+    fn correct_raw_url_generation_for_custom_site() {
+        let site = Box::leak(Box::new(CustomSite {
+            name: "corp".to_string(),
+            kind: CustomSiteKind::Gitea,
+            base_url: "https://git.example.com/".to_string(),
+            raw_url: "https://git.example.com/{qual}/{name}/raw/{ref}/{path}".to_string(),
+        }));
+
+        let repo = RepoPath {
+            site: RepoSite::Custom(site),
+            qual: "deps-rs".parse().unwrap(),
+            name: "deps.rs".parse().unwrap(),
+            git_ref: Some("main".parse().unwrap()),
+        };
+
+        let out = repo.to_usercontent_file_url(RelativePath::new("/libs/../Cargo.toml"));
+        assert_eq!(
+            out,
+            "https://git.example.com/deps-rs/deps.rs/raw/main/Cargo.toml"
+        );
+        assert_eq!(
+            repo.to_web_url(),
+            "https://git.example.com/deps-rs/deps.rs/src/main"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_git_refs() {
+        assert!("v1.0.0".parse::<GitRef>().is_ok());
+        assert!("feature/foo_bar".parse::<GitRef>().is_ok());
+        assert!("".parse::<GitRef>().is_err());
+        assert!("/main".parse::<GitRef>().is_err());
+        assert!("../main".parse::<GitRef>().is_err());
+        assert!("main?x=y".parse::<GitRef>().is_err());
+    }
+
+    #[test]
+    fn lowercases_owner_and_name_only() {
+        let repo = RepoPath::from_parts("github", "Deps-RS", "Deps.rs")
+            .unwrap()
+            .with_git_ref(Some("Feature".parse().unwrap()));
+
+        assert_eq!(
+            repo.to_lowercase(),
+            RepoPath::from_parts("github", "deps-rs", "deps.rs")
+                .unwrap()
+                .with_git_ref(Some("Feature".parse().unwrap()))
+        );
+    }
 }
@@ -0,0 +1,468 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Error};
+use lettre::message::{header::ContentType, Mailbox, Message};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use rustsec::advisory::Advisory;
+use serde::Serialize;
+use slog::{info, warn, Logger};
+use tokio::time::{self, Interval};
+
+use crate::config::{SmtpConfig, SmtpSecurity, SubscriptionConfig};
+use crate::engine::Engine;
+use crate::models::crates::CratePath;
+use crate::models::repo::RepoPath;
+use crate::models::SubjectPath;
+use crate::server::SELF_BASE_URL;
+use crate::utils::advisory_db::AdvisoryDb;
+
+/// Where notifications of a subscription are delivered
+#[derive(Debug)]
+pub enum Target {
+    /// URL that notifications are posted to as JSON
+    Webhook(String),
+    Email(Mailbox),
+}
+
+#[derive(Debug)]
+pub struct Subscription {
+    subject_path: SubjectPath,
+    targets: Vec<Target>,
+}
+
+impl Subscription {
+    pub fn from_config(config: SubscriptionConfig) -> Result<Subscription, Error> {
+        let subject_path = match (config.repo, config.krate) {
+            (Some(repo), None) => {
+                let parts: Vec<_> = repo.splitn(3, '/').collect();
+                if parts.len() != 3 {
+                    bail!("invalid repository {:?}, expected site/qual/name", repo);
+                }
+                let git_ref = config.git_ref.map(|git_ref| git_ref.parse()).transpose()?;
+                SubjectPath::Repo(
+                    RepoPath::from_parts(parts[0], parts[1], parts[2])?.with_git_ref(git_ref),
+                )
+            }
+            (None, Some(krate)) => {
+                if config.git_ref.is_some() {
+                    bail!("crate subscriptions cannot have a ref");
+                }
+                let crate_path = match *krate.split('/').collect::<Vec<_>>() {
+                    [name, version] => CratePath::from_parts(name, version)?,
+                    [registry, name, version] => {
+                        CratePath::from_parts(name, version)?.with_registry(Some(registry.parse()?))
+                    }
+                    _ => bail!(
+                        "invalid crate {:?}, expected [registry/]name/version",
+                        krate
+                    ),
+                };
+                SubjectPath::Crate(crate_path)
+            }
+            _ => bail!("subscriptions need exactly one of `repo` and `crate`"),
+        };
+
+        let mut targets = Vec::new();
+        if let Some(url) = config.webhook {
+            targets.push(Target::Webhook(url));
+        }
+        if let Some(email) = config.email {
+            targets.push(Target::Email(email.parse()?));
+        }
+        if targets.is_empty() {
+            bail!("subscriptions need a `webhook` or an `email` target");
+        }
+
+        Ok(Subscription {
+            subject_path,
+            targets,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    fn new(config: &SmtpConfig) -> Result<Mailer, Error> {
+        let mut builder = match config.security {
+            SmtpSecurity::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            }
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some(ref username) = config.username {
+            let password = config
+                .password
+                .as_ref()
+                .map(|password| password.secret().to_string())
+                .unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        Ok(Mailer {
+            transport: builder.build(),
+            from: config.from.parse()?,
+        })
+    }
+}
+
+/// Advisories that newly affect a subscribed subject
+#[derive(Debug, Serialize)]
+struct Notification<'a> {
+    subject: String,
+    url: String,
+    advisories: Vec<NotifiedAdvisory<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct NotifiedAdvisory<'a> {
+    id: &'a str,
+    #[serde(rename = "crate")]
+    krate: &'a str,
+    title: &'a str,
+    patched: Vec<String>,
+    url: String,
+}
+
+impl<'a> Notification<'a> {
+    fn new(subject_path: &SubjectPath, advisories: &[&'a Advisory]) -> Notification<'a> {
+        let (subject, query) = match subject_path {
+            SubjectPath::Repo(repo_path) => {
+                let subject = format!(
+                    "{}/{}/{}",
+                    repo_path.site.as_ref(),
+                    repo_path.qual.as_ref(),
+                    repo_path.name.as_ref()
+                );
+                match repo_path.git_ref {
+                    Some(ref git_ref) => (
+                        format!("{} ({})", subject, git_ref.as_ref()),
+                        format!("?ref={}", git_ref.as_ref()),
+                    ),
+                    None => (subject, String::new()),
+                }
+            }
+            SubjectPath::Crate(crate_path) => (
+                format!("{} {}", crate_path.name.as_ref(), crate_path.version),
+                String::new(),
+            ),
+        };
+
+        let advisories = advisories
+            .iter()
+            .map(|advisory| NotifiedAdvisory {
+                id: advisory.id().as_str(),
+                krate: advisory.metadata.package.as_str(),
+                title: advisory.title(),
+                patched: advisory
+                    .versions
+                    .patched
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                url: format!(
+                    "https://rustsec.org/advisories/{}.html",
+                    advisory.id().as_str()
+                ),
+            })
+            .collect();
+
+        Notification {
+            subject,
+            url: format!(
+                "{}/{}{}",
+                &SELF_BASE_URL as &str,
+                subject_path.path(),
+                query
+            ),
+            advisories,
+        }
+    }
+
+    fn email_subject(&self) -> String {
+        match self.advisories.as_slice() {
+            [advisory] => format!(
+                "{} affects {} via {}",
+                advisory.id, self.subject, advisory.krate
+            ),
+            advisories => format!(
+                "{} new advisories affect {}",
+                advisories.len(),
+                self.subject
+            ),
+        }
+    }
+
+    fn email_body(&self) -> String {
+        let mut body = format!(
+            "New security advisories affect the dependencies of {}.\n\n",
+            self.subject
+        );
+        for advisory in &self.advisories {
+            let patched = if advisory.patched.is_empty() {
+                "none".to_string()
+            } else {
+                advisory.patched.join(", ")
+            };
+            body.push_str(&format!(
+                "{}: {}\n  crate: {}\n  patched versions: {}\n  {}\n\n",
+                advisory.id, advisory.title, advisory.krate, patched, advisory.url
+            ));
+        }
+        body.push_str(&format!("See {} for the full analysis.\n", self.url));
+        body
+    }
+}
+
+/// Re-analyzes subscribed subjects after each refresh of the advisory database, and notifies
+/// subscribers about advisories that did not affect them before
+#[derive(Debug)]
+pub struct Notifier {
+    engine: Engine,
+    advisory_db: AdvisoryDb,
+    subscriptions: Vec<Subscription>,
+    client: reqwest::Client,
+    mailer: Option<Mailer>,
+    check_interval: Interval,
+    logger: Logger,
+}
+
+impl Notifier {
+    pub fn new(
+        engine: Engine,
+        advisory_db: AdvisoryDb,
+        subscriptions: Vec<Subscription>,
+        smtp: Option<&SmtpConfig>,
+        client: reqwest::Client,
+        check_interval: Duration,
+        logger: Logger,
+    ) -> Result<Notifier, Error> {
+        let mailer = smtp.map(Mailer::new).transpose()?;
+        let mails = subscriptions
+            .iter()
+            .flat_map(|subscription| &subscription.targets)
+            .any(|target| matches!(target, Target::Email(_)));
+        if mails && mailer.is_none() {
+            bail!("email subscriptions require an `smtp` configuration");
+        }
+
+        Ok(Notifier {
+            engine,
+            advisory_db,
+            subscriptions,
+            client,
+            mailer,
+            check_interval: time::interval(check_interval),
+            logger,
+        })
+    }
+
+    pub async fn run(mut self) {
+        // advisory IDs affecting each subscription, once it has been analyzed successfully
+        let mut known = vec![None; self.subscriptions.len()];
+        let mut checked_db = Weak::new();
+
+        loop {
+            self.check_interval.tick().await;
+
+            let db = match self.advisory_db.get() {
+                Some(db) => db,
+                None => continue,
+            };
+            if Weak::ptr_eq(&checked_db, &Arc::downgrade(&db)) {
+                continue;
+            }
+            checked_db = Arc::downgrade(&db);
+            drop(db);
+
+            for (subscription, known) in self.subscriptions.iter().zip(&mut known) {
+                self.check(subscription, known).await;
+            }
+        }
+    }
+
+    async fn check(&self, subscription: &Subscription, known: &mut Option<BTreeSet<String>>) {
+        let outcome = match self
+            .engine
+            .analyze_fresh(subscription.subject_path.clone())
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                warn!(
+                    self.logger,
+                    "failed to analyze subscribed {}: {}",
+                    subscription.subject_path.path(),
+                    err
+                );
+                return;
+            }
+        };
+
+        let vulnerabilities = outcome.vulnerabilities();
+        let new_advisories = new_advisories(known.as_ref(), &vulnerabilities);
+        *known = Some(
+            vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().to_string())
+                .collect(),
+        );
+        if new_advisories.is_empty() {
+            return;
+        }
+
+        let notification = Notification::new(&subscription.subject_path, &new_advisories);
+        info!(
+            self.logger, "notifying subscribers of new advisories";
+            "subject" => &notification.subject,
+            "advisories" => new_advisories.len()
+        );
+        for target in &subscription.targets {
+            if let Err(err) = self.deliver(target, &notification).await {
+                warn!(
+                    self.logger,
+                    "failed to notify {}: {:#}", notification.subject, err
+                );
+            }
+        }
+    }
+
+    async fn deliver(&self, target: &Target, notification: &Notification<'_>) -> Result<(), Error> {
+        match target {
+            Target::Webhook(url) => {
+                self.client
+                    .post(url)
+                    .json(notification)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Target::Email(to) => {
+                let mailer = self
+                    .mailer
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("no SMTP server configured"))?;
+                let message = Message::builder()
+                    .from(mailer.from.clone())
+                    .to(to.clone())
+                    .subject(notification.email_subject())
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(notification.email_body())?;
+                mailer.transport.send(message).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the advisories that are not among the known ones. Nothing is new until the known
+/// advisories have been determined once.
+fn new_advisories<'a>(
+    known: Option<&BTreeSet<String>>,
+    vulnerabilities: &[&'a Advisory],
+) -> Vec<&'a Advisory> {
+    match known {
+        Some(known) => vulnerabilities
+            .iter()
+            .filter(|advisory| !known.contains(advisory.id().as_str()))
+            .copied()
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::crates::test_advisory;
+    use crate::models::repo::{CustomSite, CustomSiteKind};
+
+    fn subscription(toml: &str) -> Result<Subscription, Error> {
+        Subscription::from_config(toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn parses_subscriptions() {
+        let sub = subscription(
+            r#"
+                repo = "github/deps-rs/deps.rs"
+                ref = "develop"
+                webhook = "https://example.com/hook"
+                email = "Ops <ops@example.com>"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(sub.subject_path.path(), "repo/github/deps-rs/deps.rs");
+        assert_eq!(sub.targets.len(), 2);
+
+        let sub = subscription("crate = 'hyper/0.14.2'\nwebhook = 'https://example.com'");
+        assert!(sub.is_ok());
+
+        assert!(subscription("repo = 'github/deps-rs'\nwebhook = 'https://example.com'").is_err());
+        assert!(subscription(r#"crate = "hyper/0.14.2""#).is_err());
+        assert!(subscription(
+            "repo = 'github/deps-rs/deps.rs'\ncrate = 'hyper/0.14.2'\nwebhook = 'https://example.com'"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parses_subscriptions_for_custom_sites() {
+        CustomSite::register(vec![CustomSite {
+            name: "corp".to_string(),
+            kind: CustomSiteKind::Gitea,
+            base_url: "https://git.example.com".to_string(),
+            raw_url: "https://git.example.com/{qual}/{name}/raw/{ref}/{path}".to_string(),
+        }])
+        .unwrap();
+
+        let sub = subscription("repo = 'corp/deps-rs/deps.rs'\nwebhook = 'https://example.com'");
+        assert_eq!(
+            sub.unwrap().subject_path.path(),
+            "repo/corp/deps-rs/deps.rs"
+        );
+    }
+
+    #[test]
+    fn notifies_only_unknown_advisories() {
+        let advisory = test_advisory();
+        let vulnerabilities = vec![&advisory];
+
+        assert!(new_advisories(None, &vulnerabilities).is_empty());
+
+        let known = BTreeSet::new();
+        let new = new_advisories(Some(&known), &vulnerabilities);
+        assert_eq!(new.len(), 1);
+
+        let known = std::iter::once("RUSTSEC-2021-0020".to_string()).collect();
+        assert!(new_advisories(Some(&known), &vulnerabilities).is_empty());
+
+        let repo_path = RepoPath::from_parts("github", "deps-rs", "deps.rs").unwrap();
+        let notification = Notification::new(&SubjectPath::Repo(repo_path), &new);
+        let json = serde_json::to_value(&notification).unwrap();
+        assert_eq!(json["subject"], "github/deps-rs/deps.rs");
+        assert_eq!(json["advisories"][0]["id"], "RUSTSEC-2021-0020");
+        assert_eq!(json["advisories"][0]["crate"], "hyper");
+        assert_eq!(
+            json["advisories"][0]["patched"],
+            serde_json::json!([">=0.14.3", ">=0.13.10, <0.14.0"])
+        );
+        assert_eq!(
+            notification.email_subject(),
+            "RUSTSEC-2021-0020 affects github/deps-rs/deps.rs via hyper"
+        );
+    }
+}
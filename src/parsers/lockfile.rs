@@ -0,0 +1,67 @@
+use anyhow::Error;
+use rustsec::cargo_lock::Lockfile;
+
+use crate::models::crates::LockedPackage;
+
+/// Parses a `Cargo.lock`, returning the packages that are pulled from crates.io
+pub fn parse_lockfile(input: &str) -> Result<Vec<LockedPackage>, Error> {
+    let lockfile = input.parse::<Lockfile>()?;
+
+    lockfile
+        .packages
+        .into_iter()
+        .filter(
+            |package| matches!(package.source, Some(ref source) if source.is_default_registry()),
+        )
+        .map(|package| {
+            Ok(LockedPackage {
+                name: package.name.as_str().parse()?,
+                version: package.version.to_string().parse()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_registry_packages_only() {
+        let lockfile = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "smallvec",
+ "local-util",
+ "patched",
+]
+
+[[package]]
+name = "local-util"
+version = "0.1.0"
+
+[[package]]
+name = "patched"
+version = "0.3.0"
+source = "git+https://github.com/example/patched#0123456789abcdef0123456789abcdef01234567"
+
+[[package]]
+name = "smallvec"
+version = "1.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "fe0f37c9e8f3c5a4a66ad655a93c74daac4ad00c441533bf5c6e7990bb42604e"
+"#;
+
+        let packages = parse_lockfile(lockfile).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![LockedPackage {
+                name: "smallvec".parse().unwrap(),
+                version: "1.6.0".parse().unwrap(),
+            }]
+        );
+    }
+}
@@ -1,17 +1,30 @@
 use anyhow::{anyhow, Error};
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use relative_path::RelativePathBuf;
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 
-use crate::models::crates::{CrateDep, CrateDeps, CrateManifest, CrateName};
+use crate::models::crates::{
+    CrateDep, CrateDepFeatures, CrateDepOptional, CrateDepPlatforms, CrateDepTargets, CrateDeps,
+    CrateManifest, CrateName, DepKind, GitDep, GitPin, WorkspaceDep, WorkspaceDeps,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct CargoTomlComplexDependency {
     git: Option<String>,
+    branch: Option<String>,
+    tag: Option<String>,
+    rev: Option<String>,
     path: Option<RelativePathBuf>,
     version: Option<String>,
     package: Option<String>,
+    registry: Option<String>,
+    #[serde(default)]
+    workspace: bool,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    features: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -30,6 +43,22 @@ struct CargoTomlPackage {
 struct CargoTomlWorkspace {
     #[serde(default)]
     members: Vec<RelativePathBuf>,
+    #[serde(default)]
+    exclude: Vec<RelativePathBuf>,
+    #[serde(default)]
+    dependencies: IndexMap<String, CargoTomlDependency>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CargoTomlTarget {
+    #[serde(default)]
+    dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(rename = "dev-dependencies")]
+    #[serde(default)]
+    dev_dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(rename = "build-dependencies")]
+    #[serde(default)]
+    build_dependencies: IndexMap<String, CargoTomlDependency>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -46,6 +75,8 @@ struct CargoToml {
     #[serde(rename = "build-dependencies")]
     #[serde(default)]
     build_dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(default)]
+    target: IndexMap<String, CargoTomlTarget>,
 }
 
 fn convert_dependency(
@@ -61,28 +92,99 @@ fn convert_dependency(
             }))
         }
         (name, CargoTomlDependency::Complex(cplx)) => {
-            if cplx.git.is_some() {
-                None
+            if cplx.workspace {
+                Some(
+                    name.parse::<CrateName>()
+                        .map(|parsed_name| (parsed_name, CrateDep::Inherited)),
+                )
+            } else if let Some(url) = cplx.git {
+                let pin = match (cplx.rev, cplx.tag, cplx.branch) {
+                    (Some(rev), _, _) => GitPin::Rev(rev),
+                    (_, Some(tag), _) => GitPin::Tag(tag),
+                    (_, _, Some(branch)) => GitPin::Branch(branch),
+                    (None, None, None) => GitPin::DefaultBranch,
+                };
+                let name = cplx.package.as_deref().unwrap_or(&name);
+                Some(
+                    name.parse::<CrateName>()
+                        .map(|parsed_name| (parsed_name, CrateDep::Git(GitDep { url, pin }))),
+                )
             } else if cplx.path.is_some() {
                 cplx.path.map(|path| {
                     name.parse::<CrateName>()
                         .map(|parsed_name| (parsed_name, CrateDep::Internal(path)))
                 })
             } else {
+                let registry = cplx.registry.as_deref();
                 cplx.version.as_deref().map(|version| {
                     let name = cplx.package.as_deref().unwrap_or(&name);
-                    name.parse::<CrateName>().and_then(|parsed_name| {
-                        version
-                            .parse::<VersionReq>()
-                            .map_err(|err| err.into())
-                            .map(|version| (parsed_name, CrateDep::External(version)))
-                    })
+                    let parsed_name = name.parse::<CrateName>()?;
+                    let version = version.parse::<VersionReq>()?;
+                    let dep = match registry {
+                        Some(registry) => CrateDep::Registry(registry.parse()?, version),
+                        None => CrateDep::External(version),
+                    };
+                    Ok((parsed_name, dep))
                 })
             }
         }
     }
 }
 
+fn dep_features(cplx: &CargoTomlComplexDependency) -> IndexSet<String> {
+    cplx.features.iter().cloned().collect()
+}
+
+/// Adds the dependencies of a `[target.*]` table, remembering the platform of each one
+fn merge_target_dependencies(
+    deps: &mut CrateDeps,
+    kind: DepKind,
+    target: &str,
+    cargo_deps: IndexMap<String, CargoTomlDependency>,
+) -> Result<(), Error> {
+    let mut optional = IndexSet::new();
+    let mut features = IndexMap::new();
+    let target_deps = convert_dependencies(cargo_deps, &mut optional, &mut features)?;
+
+    for (name, dep) in target_deps {
+        let is_optional = optional.contains(&name);
+        let dep_features = features.swap_remove(&name).unwrap_or_default();
+        deps.insert(kind, name, dep, Some(target), is_optional, dep_features);
+    }
+
+    Ok(())
+}
+
+/// Converts a dependency table, remembering which dependencies are optional and the
+/// features they enable
+fn convert_dependencies(
+    cargo_deps: IndexMap<String, CargoTomlDependency>,
+    optional: &mut IndexSet<CrateName>,
+    features: &mut IndexMap<CrateName, IndexSet<String>>,
+) -> Result<IndexMap<CrateName, CrateDep>, Error> {
+    let mut deps = IndexMap::new();
+
+    for cargo_dep in cargo_deps {
+        let (is_optional, dep_features) = match cargo_dep.1 {
+            CargoTomlDependency::Complex(ref cplx) => (cplx.optional, dep_features(cplx)),
+            CargoTomlDependency::Simple(_) => (false, IndexSet::new()),
+        };
+
+        if let Some(converted) = convert_dependency(cargo_dep) {
+            let (name, dep) = converted?;
+            if is_optional {
+                optional.insert(name.clone());
+            }
+            if !dep_features.is_empty() {
+                features.insert(name.clone(), dep_features);
+            }
+            deps.insert(name, dep);
+        }
+    }
+
+    Ok(deps)
+}
+
 pub fn parse_manifest_toml(input: &str) -> Result<CrateManifest, Error> {
     let cargo_toml = toml::de::from_str::<CargoToml>(input)?;
 
@@ -92,50 +194,106 @@ pub fn parse_manifest_toml(input: &str) -> Result<CrateManifest, Error> {
     if let Some(package) = cargo_toml.package {
         let crate_name = package.name.parse::<CrateName>()?;
 
-        let dependencies = cargo_toml
-            .dependencies
-            .into_iter()
-            .filter_map(convert_dependency)
-            .collect::<Result<IndexMap<_, _>, _>>()?;
-        let dev_dependencies = cargo_toml
-            .dev_dependencies
-            .into_iter()
-            .filter_map(convert_dependency)
-            .collect::<Result<IndexMap<_, _>, _>>()?;
-        let build_dependencies = cargo_toml
-            .build_dependencies
-            .into_iter()
-            .filter_map(convert_dependency)
-            .collect::<Result<IndexMap<_, _>, _>>()?;
-
-        let deps = CrateDeps {
+        let mut optional = CrateDepOptional::default();
+        let mut features = CrateDepFeatures::default();
+
+        let dependencies = convert_dependencies(
+            cargo_toml.dependencies,
+            &mut optional.main,
+            &mut features.main,
+        )?;
+        let dev_dependencies = convert_dependencies(
+            cargo_toml.dev_dependencies,
+            &mut optional.dev,
+            &mut features.dev,
+        )?;
+        let build_dependencies = convert_dependencies(
+            cargo_toml.build_dependencies,
+            &mut optional.build,
+            &mut features.build,
+        )?;
+
+        let mut deps = CrateDeps {
             main: dependencies,
             dev: dev_dependencies,
             build: build_dependencies,
+            targets: CrateDepTargets::default(),
+            optional,
+            features,
+            platforms: CrateDepPlatforms::default(),
         };
 
+        for (target, target_deps) in cargo_toml.target {
+            merge_target_dependencies(
+                &mut deps,
+                DepKind::Normal,
+                &target,
+                target_deps.dependencies,
+            )?;
+            merge_target_dependencies(
+                &mut deps,
+                DepKind::Dev,
+                &target,
+                target_deps.dev_dependencies,
+            )?;
+            merge_target_dependencies(
+                &mut deps,
+                DepKind::Build,
+                &target,
+                target_deps.build_dependencies,
+            )?;
+        }
+
         package_part = Some((crate_name, deps));
     }
 
     if let Some(workspace) = cargo_toml.workspace {
-        workspace_part = Some(workspace.members);
+        let mut workspace_deps = WorkspaceDeps::new();
+        for (key, cargo_dep) in workspace.dependencies {
+            let parsed_key = key.parse::<CrateName>()?;
+            let features = match cargo_dep {
+                CargoTomlDependency::Complex(ref cplx) => dep_features(cplx),
+                CargoTomlDependency::Simple(_) => IndexSet::new(),
+            };
+            if let Some(converted) = convert_dependency((key, cargo_dep)) {
+                let (name, dep) = converted?;
+                workspace_deps.insert(
+                    parsed_key,
+                    WorkspaceDep {
+                        name,
+                        dep,
+                        features,
+                    },
+                );
+            }
+        }
+
+        workspace_part = Some((workspace.members, workspace.exclude, workspace_deps));
     }
 
     match (package_part, workspace_part) {
         (Some((name, deps)), None) => Ok(CrateManifest::Package(name, deps)),
-        (None, Some(members)) => Ok(CrateManifest::Workspace { members }),
-        (Some((name, deps)), Some(members)) => Ok(CrateManifest::Mixed {
-            name,
-            deps,
+        (None, Some((members, exclude, workspace_deps))) => Ok(CrateManifest::Workspace {
             members,
+            exclude,
+            workspace_deps,
         }),
+        (Some((name, deps)), Some((members, exclude, workspace_deps))) => {
+            Ok(CrateManifest::Mixed {
+                name,
+                deps,
+                members,
+                exclude,
+                workspace_deps,
+            })
+        }
         (None, None) => Err(anyhow!("neither workspace nor package found in manifest")),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::models::crates::CrateManifest;
+    use crate::models::crates::{AnalyzedDependencies, CrateManifest};
 
     use super::*;
 
@@ -157,6 +315,7 @@ symbolic-common = { version = "2.0.6", path = "common" }
                 name,
                 deps,
                 members,
+                ..
             } => {
                 assert_eq!(name.as_ref(), "symbolic");
                 assert_eq!(deps.main.len(), 1);
@@ -192,4 +351,228 @@ symbolic-common_crate = { version = "2.0.6", package = "symbolic-common" }
             _ => panic!("expected package manifest"),
         }
     }
+
+    #[test]
+    fn parse_manifest_with_registry_deps() {
+        let toml = r#"[package]
+name = "service"
+
+[dependencies]
+internal-auth = { version = "1.2", registry = "corp" }
+serde = "1"
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(
+                    deps.main.get("internal-auth").unwrap(),
+                    &CrateDep::Registry("corp".parse().unwrap(), "1.2".parse().unwrap())
+                );
+                assert!(matches!(
+                    deps.main.get("serde").unwrap(),
+                    CrateDep::External(_)
+                ));
+            }
+            _ => panic!("expected package manifest"),
+        }
+    }
+
+    #[test]
+    fn parse_manifest_with_git_deps() {
+        let toml = r#"[package]
+name = "service"
+
+[dependencies]
+hyper = { git = "https://github.com/hyperium/hyper", branch = "master" }
+tokio_git = { git = "https://github.com/tokio-rs/tokio", rev = "abc123", package = "tokio" }
+
+[dev-dependencies]
+insta = { git = "https://github.com/mitsuhiko/insta" }
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(
+                    deps.main.get("hyper").unwrap(),
+                    &CrateDep::Git(GitDep {
+                        url: "https://github.com/hyperium/hyper".to_string(),
+                        pin: GitPin::Branch("master".to_string()),
+                    })
+                );
+                assert!(matches!(
+                    deps.main.get("tokio").unwrap(),
+                    CrateDep::Git(GitDep {
+                        pin: GitPin::Rev(_),
+                        ..
+                    })
+                ));
+                assert!(matches!(
+                    deps.dev.get("insta").unwrap(),
+                    CrateDep::Git(GitDep {
+                        pin: GitPin::DefaultBranch,
+                        ..
+                    })
+                ));
+            }
+            _ => panic!("expected package manifest"),
+        }
+    }
+
+    #[test]
+    fn parse_workspace_with_excludes() {
+        let toml = r#"[workspace]
+members = ["crates/*"]
+exclude = ["crates/experimental"]
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Workspace {
+                members, exclude, ..
+            } => {
+                assert_eq!(members, vec![RelativePathBuf::from("crates/*")]);
+                assert_eq!(exclude, vec![RelativePathBuf::from("crates/experimental")]);
+            }
+            _ => panic!("expected workspace manifest"),
+        }
+    }
+
+    #[test]
+    fn parse_workspace_dependencies() {
+        let toml = r#"[workspace]
+members = ["app"]
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+futures_legacy = { version = "0.1", package = "futures" }
+app-core = { path = "core" }
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Workspace { workspace_deps, .. } => {
+                assert_eq!(workspace_deps.len(), 3);
+                let serde = workspace_deps.get("serde").unwrap();
+                assert_eq!(serde.name.as_ref(), "serde");
+                assert_eq!(serde.dep, CrateDep::External("1.0".parse().unwrap()));
+                assert!(serde.features.contains("derive"));
+                let futures = workspace_deps.get("futures_legacy").unwrap();
+                assert_eq!(futures.name.as_ref(), "futures");
+                assert_eq!(futures.dep, CrateDep::External("0.1".parse().unwrap()));
+                assert!(futures.features.is_empty());
+                assert_eq!(
+                    workspace_deps.get("app-core").unwrap().dep,
+                    CrateDep::Internal("core".into())
+                );
+            }
+            _ => panic!("expected workspace manifest"),
+        }
+    }
+
+    #[test]
+    fn parse_manifest_with_inherited_deps() {
+        let toml = r#"[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true, features = ["rc"] }
+futures_legacy.workspace = true
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(deps.main.len(), 2);
+                assert_eq!(deps.main.get("serde").unwrap(), &CrateDep::Inherited);
+                assert!(deps.features.main["serde"].contains("rc"));
+                assert_eq!(
+                    deps.main.get("futures_legacy").unwrap(),
+                    &CrateDep::Inherited
+                );
+            }
+            _ => panic!("expected package manifest"),
+        }
+    }
+
+    #[test]
+    fn parse_manifest_with_target_deps() {
+        let toml = r#"[package]
+name = "platform"
+
+[dependencies]
+libc = "0.2"
+serde = { version = "1", optional = true }
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2.80", optional = true }
+nix = "0.20"
+
+[target.'cfg(windows)'.dependencies]
+winapi = { version = "0.3", features = ["winuser"] }
+
+[target.'cfg(target_arch = "wasm32")'.dependencies]
+winapi = "0.3"
+
+[target.'cfg(target_os = "redox")'.dependencies]
+winapi = "0.2"
+
+[build-dependencies]
+serde = "1"
+
+[target.x86_64-pc-windows-gnu.dev-dependencies]
+tempfile = "3"
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(deps.main.len(), 4);
+                assert!(deps.optional.main.contains("serde"));
+                assert!(!deps.optional.main.contains("libc"));
+                assert!(!deps.optional.build.contains("serde"));
+                assert_eq!(
+                    deps.main.get("libc").unwrap(),
+                    &CrateDep::External("0.2".parse().unwrap())
+                );
+                assert_eq!(deps.targets.main.get("libc"), None);
+                assert_eq!(deps.targets.main.get("nix").unwrap(), "cfg(unix)");
+                assert_eq!(
+                    deps.targets.main.get("winapi").unwrap(),
+                    r#"cfg(windows), cfg(target_arch = "wasm32")"#
+                );
+                assert!(deps.features.main["winapi"].contains("winuser"));
+                assert_eq!(deps.dev.len(), 1);
+
+                // platforms requiring another version are analyzed as well
+                let analyzed = AnalyzedDependencies::new(&deps);
+                let libc = &analyzed.main["libc"];
+                assert_eq!(libc.target, None);
+                assert_eq!(libc.platforms.len(), 1);
+                assert_eq!(libc.platforms[0].required, "0.2.80".parse().unwrap());
+                assert_eq!(libc.platforms[0].target.as_deref(), Some("cfg(unix)"));
+                assert!(libc.platforms[0].optional);
+                let winapi = &analyzed.main["winapi"];
+                assert_eq!(winapi.platforms.len(), 1);
+                assert_eq!(winapi.platforms[0].required, "0.2".parse().unwrap());
+                assert_eq!(
+                    winapi.platforms[0].target.as_deref(),
+                    Some(r#"cfg(target_os = "redox")"#)
+                );
+                assert_eq!(analyzed.count_total(), 7);
+                assert_eq!(
+                    deps.targets.dev.get("tempfile").unwrap(),
+                    "x86_64-pc-windows-gnu"
+                );
+            }
+            _ => panic!("expected package manifest"),
+        }
+    }
 }
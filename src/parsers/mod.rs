@@ -1 +1,3 @@
+pub mod lockfile;
 pub mod manifest;
+pub mod policy;
@@ -0,0 +1,123 @@
+use anyhow::Error;
+use serde::Deserialize;
+
+use crate::models::crates::{AnalysisPolicy, CrateName};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DepsToml {
+    #[serde(default)]
+    ignore: Vec<String>,
+    #[serde(default)]
+    ignore_advisories: Vec<String>,
+    #[serde(default)]
+    exclude_dev: bool,
+    #[serde(default)]
+    pinned: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoTomlMetadata {
+    #[serde(rename = "deps-rs")]
+    deps_rs: Option<DepsToml>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoTomlSection {
+    metadata: Option<CargoTomlMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoToml {
+    package: Option<CargoTomlSection>,
+    workspace: Option<CargoTomlSection>,
+}
+
+fn convert_policy(deps_toml: DepsToml) -> Result<AnalysisPolicy, Error> {
+    let parse_names = |names: Vec<String>| {
+        names
+            .iter()
+            .map(|name| name.parse::<CrateName>())
+            .collect::<Result<_, _>>()
+    };
+
+    Ok(AnalysisPolicy {
+        ignore: parse_names(deps_toml.ignore)?,
+        ignore_advisories: deps_toml.ignore_advisories.into_iter().collect(),
+        exclude_dev: deps_toml.exclude_dev,
+        pinned: parse_names(deps_toml.pinned)?,
+    })
+}
+
+/// Parses a `.deps.toml` at the root of a repository
+pub fn parse_policy_toml(input: &str) -> Result<AnalysisPolicy, Error> {
+    convert_policy(toml::de::from_str(input)?)
+}
+
+/// Parses the `[package.metadata.deps-rs]` or `[workspace.metadata.deps-rs]` table of a
+/// manifest, if it has one
+pub fn parse_manifest_policy(input: &str) -> Result<Option<AnalysisPolicy>, Error> {
+    let cargo_toml = toml::de::from_str::<CargoToml>(input)?;
+
+    let deps_toml = cargo_toml
+        .package
+        .into_iter()
+        .chain(cargo_toml.workspace)
+        .filter_map(|section| section.metadata?.deps_rs)
+        .next();
+
+    deps_toml.map(convert_policy).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_deps_toml() {
+        let toml = r#"
+ignore = ["openssl-sys"]
+ignore-advisories = ["RUSTSEC-2020-0071"]
+exclude-dev = true
+pinned = ["hyper"]
+"#;
+
+        let policy = parse_policy_toml(toml).unwrap();
+        assert!(policy.ignore.contains("openssl-sys"));
+        assert!(policy.ignore_advisories.contains("RUSTSEC-2020-0071"));
+        assert!(policy.exclude_dev);
+        assert!(policy.pinned.contains("hyper"));
+
+        assert!(parse_policy_toml("pin = [\"hyper\"]").is_err());
+        assert_eq!(parse_policy_toml("").unwrap(), AnalysisPolicy::default());
+    }
+
+    #[test]
+    fn parse_policy_from_manifest_metadata() {
+        let toml = r#"[package]
+name = "app"
+
+[package.metadata.deps-rs]
+pinned = ["hyper"]
+"#;
+        let policy = parse_manifest_policy(toml).unwrap().unwrap();
+        assert!(policy.pinned.contains("hyper"));
+
+        let toml = r#"[workspace]
+members = ["app"]
+
+[workspace.metadata.deps-rs]
+exclude-dev = true
+"#;
+        let policy = parse_manifest_policy(toml).unwrap().unwrap();
+        assert!(policy.exclude_dev);
+
+        let toml = r#"[package]
+name = "app"
+
+[package.metadata.docs.rs]
+all-features = true
+"#;
+        assert_eq!(parse_manifest_policy(toml).unwrap(), None);
+    }
+}
@@ -1,8 +1,9 @@
 use std::{env, sync::Arc, time::Instant};
 
+use anyhow::Error;
 use futures::future;
 use hyper::{
-    header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LOCATION},
+    header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LOCATION},
     Body, Error as HyperError, Method, Request, Response, StatusCode,
 };
 use once_cell::sync::Lazy;
@@ -11,18 +12,72 @@ use semver::VersionReq;
 use slog::{error, info, o, Logger};
 
 mod assets;
-mod views;
+pub mod views;
+mod webhook;
 
 use self::assets::{STATIC_STYLE_CSS_ETAG, STATIC_STYLE_CSS_PATH};
-use crate::engine::{AnalyzeDependenciesOutcome, Engine};
+use self::views::badge::{BadgeAppearance, BadgeKind};
+use crate::config::SiteTokens;
+use crate::engine::{AnalysisFailure, AnalyzeDependenciesOutcome, Engine};
 use crate::models::crates::{CrateName, CratePath};
-use crate::models::repo::RepoPath;
+use crate::models::repo::{GitRef, RepoPath, RepoSite};
 use crate::models::SubjectPath;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum StatusFormat {
     Html,
     Svg,
+    Png,
+    Json,
+    Shields,
+}
+
+#[derive(Debug, Default)]
+struct StatusQuery {
+    git_ref: Option<String>,
+    transitive: bool,
+    kind: Option<String>,
+    style: Option<String>,
+    subject: Option<String>,
+    logo: Option<String>,
+}
+
+impl StatusQuery {
+    /// Reads the query string parameter by parameter, so that an invalid one doesn't
+    /// discard the others
+    fn from_request(req: &Request<Body>) -> StatusQuery {
+        let params: Vec<(String, String)> = req
+            .uri()
+            .query()
+            .and_then(|query| serde_urlencoded::from_str(query).ok())
+            .unwrap_or_default();
+
+        let mut query = StatusQuery::default();
+        for (key, value) in params {
+            match key.as_str() {
+                "ref" => query.git_ref = Some(value),
+                "transitive" => query.transitive = value.parse().unwrap_or_default(),
+                "kind" => query.kind = Some(value),
+                "style" => query.style = Some(value),
+                "subject" => query.subject = Some(value),
+                "logo" => query.logo = Some(value),
+                _ => {}
+            }
+        }
+        query
+    }
+
+    fn badge_kind(&self) -> BadgeKind {
+        BadgeKind::from_query(self.kind.as_deref())
+    }
+
+    fn badge_appearance(&self) -> BadgeAppearance {
+        BadgeAppearance::from_query(
+            self.style.as_deref(),
+            self.subject.as_deref(),
+            self.logo.as_deref(),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -31,23 +86,28 @@ enum StaticFile {
     FaviconPng,
 }
 
+#[derive(Debug)]
 enum Route {
     Index,
     Static(StaticFile),
     RepoStatus(StatusFormat),
+    RepoHistory,
+    Webhook,
     CrateRedirect,
     CrateStatus(StatusFormat),
+    CrateHistory,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct App {
     logger: Logger,
     engine: Engine,
+    webhook_secrets: SiteTokens,
     router: Arc<Router<Route>>,
 }
 
 impl App {
-    pub fn new(logger: Logger, engine: Engine) -> App {
+    pub fn new(logger: Logger, engine: Engine, webhook_secrets: SiteTokens) -> App {
         let mut router = Router::new();
 
         router.add("/", Route::Index);
@@ -63,6 +123,21 @@ impl App {
             "/repo/:site/:qual/:name/status.svg",
             Route::RepoStatus(StatusFormat::Svg),
         );
+        router.add(
+            "/repo/:site/:qual/:name/status.png",
+            Route::RepoStatus(StatusFormat::Png),
+        );
+        router.add(
+            "/repo/:site/:qual/:name/status.json",
+            Route::RepoStatus(StatusFormat::Json),
+        );
+        router.add(
+            "/repo/:site/:qual/:name/shields.json",
+            Route::RepoStatus(StatusFormat::Shields),
+        );
+        router.add("/repo/:site/:qual/:name/history", Route::RepoHistory);
+
+        router.add("/hooks/:site", Route::Webhook);
 
         router.add("/crate/:name", Route::CrateRedirect);
         router.add(
@@ -73,10 +148,48 @@ impl App {
             "/crate/:name/:version/status.svg",
             Route::CrateStatus(StatusFormat::Svg),
         );
+        router.add(
+            "/crate/:name/:version/status.png",
+            Route::CrateStatus(StatusFormat::Png),
+        );
+        router.add(
+            "/crate/:name/:version/status.json",
+            Route::CrateStatus(StatusFormat::Json),
+        );
+        router.add(
+            "/crate/:name/:version/shields.json",
+            Route::CrateStatus(StatusFormat::Shields),
+        );
+        router.add(
+            "/crate/:registry/:name/:version",
+            Route::CrateStatus(StatusFormat::Html),
+        );
+        router.add(
+            "/crate/:registry/:name/:version/status.svg",
+            Route::CrateStatus(StatusFormat::Svg),
+        );
+        router.add(
+            "/crate/:registry/:name/:version/status.png",
+            Route::CrateStatus(StatusFormat::Png),
+        );
+        router.add(
+            "/crate/:registry/:name/:version/status.json",
+            Route::CrateStatus(StatusFormat::Json),
+        );
+        router.add(
+            "/crate/:registry/:name/:version/shields.json",
+            Route::CrateStatus(StatusFormat::Shields),
+        );
+        router.add("/crate/:name/:version/history", Route::CrateHistory);
+        router.add(
+            "/crate/:registry/:name/:version/history",
+            Route::CrateHistory,
+        );
 
         App {
             logger,
             engine,
+            webhook_secrets,
             router: Arc::new(router),
         }
     }
@@ -100,11 +213,26 @@ impl App {
                         .await
                 }
 
+                (&Method::GET, Route::RepoHistory) => {
+                    self.repo_history(req, route_match.params().clone(), logger)
+                        .await
+                }
+
+                (&Method::POST, Route::Webhook) => {
+                    self.webhook(req, route_match.params().clone(), logger)
+                        .await
+                }
+
                 (&Method::GET, Route::CrateStatus(format)) => {
                     self.crate_status(req, route_match.params().clone(), logger, *format)
                         .await
                 }
 
+                (&Method::GET, Route::CrateHistory) => {
+                    self.crate_history(route_match.params().clone(), logger)
+                        .await
+                }
+
                 (&Method::GET, Route::CrateRedirect) => {
                     self.crate_redirect(req, route_match.params().clone(), logger)
                         .await
@@ -162,25 +290,19 @@ impl App {
 
     async fn repo_status(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
         params: Params,
         logger: Logger,
         format: StatusFormat,
     ) -> Result<Response<Body>, HyperError> {
         let server = self.clone();
 
-        let site = params.find("site").expect("route param 'site' not found");
-        let qual = params.find("qual").expect("route param 'qual' not found");
-        let name = params.find("name").expect("route param 'name' not found");
-
-        let repo_path_result = RepoPath::from_parts(site, qual, name);
-
-        match repo_path_result {
+        match parse_repo_path(&req, &params) {
             Err(err) => {
                 error!(logger, "error: {}", err);
                 let mut response = views::html::error::render(
                     "Could not parse repository path",
-                    "Please make sure to provide a valid repository path.",
+                    "Please make sure to provide a valid repository path and ref.",
                 );
                 *response.status_mut() = StatusCode::BAD_REQUEST;
                 Ok(response)
@@ -192,26 +314,153 @@ impl App {
                     .analyze_repo_dependencies(repo_path.clone())
                     .await;
 
-                match analyze_result {
-                    Err(err) => {
-                        error!(logger, "error: {}", err);
-                        let response =
-                            App::status_format_analysis(None, format, SubjectPath::Repo(repo_path));
-                        Ok(response)
-                    }
-                    Ok(analysis_outcome) => {
-                        let response = App::status_format_analysis(
-                            Some(analysis_outcome),
-                            format,
-                            SubjectPath::Repo(repo_path),
-                        );
-                        Ok(response)
-                    }
+                if let Err(ref err) = analyze_result {
+                    error!(logger, "error: {}", err);
                 }
+
+                let response = App::status_format_analysis(
+                    analyze_result.as_deref(),
+                    format,
+                    SubjectPath::Repo(repo_path),
+                    &StatusQuery::from_request(&req),
+                );
+                Ok(response)
             }
         }
     }
 
+    async fn repo_history(
+        &self,
+        req: Request<Body>,
+        params: Params,
+        logger: Logger,
+    ) -> Result<Response<Body>, HyperError> {
+        let repo_path = match parse_repo_path(&req, &params) {
+            Ok(repo_path) => repo_path,
+            Err(err) => {
+                error!(logger, "error: {}", err);
+                let mut response = views::html::error::render(
+                    "Could not parse repository path",
+                    "Please make sure to provide a valid repository path and ref.",
+                );
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                return Ok(response);
+            }
+        };
+
+        self.history(SubjectPath::Repo(repo_path), logger).await
+    }
+
+    async fn crate_history(
+        &self,
+        params: Params,
+        logger: Logger,
+    ) -> Result<Response<Body>, HyperError> {
+        let crate_path = match parse_crate_path(&params) {
+            Ok(crate_path) => crate_path,
+            Err(err) => {
+                error!(logger, "error: {}", err);
+                let mut response = views::html::error::render(
+                    "Could not parse crate path",
+                    "Please make sure to provide a valid crate name and version.",
+                );
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                return Ok(response);
+            }
+        };
+
+        self.history(SubjectPath::Crate(crate_path), logger).await
+    }
+
+    async fn history(
+        &self,
+        subject_path: SubjectPath,
+        logger: Logger,
+    ) -> Result<Response<Body>, HyperError> {
+        match self.engine.history(&subject_path).await {
+            Err(err) => {
+                error!(logger, "error: {}", err);
+                let mut response = views::html::error::render(
+                    "Could not load the analysis history",
+                    "Please try again later.",
+                );
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                Ok(response)
+            }
+            Ok(entries) => Ok(views::html::history::render(&subject_path, entries)),
+        }
+    }
+
+    async fn webhook(
+        &self,
+        req: Request<Body>,
+        params: Params,
+        logger: Logger,
+    ) -> Result<Response<Body>, HyperError> {
+        let site = params.find("site").expect("route param 'site' not found");
+
+        // only sites with a configured secret accept webhooks
+        let site_and_secret = site.parse::<RepoSite>().ok().and_then(|site| {
+            let provider = webhook::Provider::of_site(&site)?;
+            let secret = self.webhook_secrets.get(&site)?.clone();
+            Some((site, provider, secret))
+        });
+        let (site, provider, secret) = match site_and_secret {
+            Some(found) => found,
+            None => return Ok(not_found()),
+        };
+
+        let content_length = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<usize>().ok());
+        match content_length {
+            Some(len) if len <= MAX_WEBHOOK_PAYLOAD => {}
+            Some(_) => {
+                return Ok(plain_text(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "payload too large",
+                ))
+            }
+            None => return Ok(plain_text(StatusCode::LENGTH_REQUIRED, "length required")),
+        }
+
+        let (parts, body) = req.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+
+        if !provider.verify(&parts.headers, &body, &secret) {
+            return Ok(plain_text(StatusCode::UNAUTHORIZED, "invalid signature"));
+        }
+        if !provider.is_push(&parts.headers) {
+            return Ok(plain_text(StatusCode::OK, "ignored"));
+        }
+
+        let push = match provider.parse_push(site, &body) {
+            Ok(push) => push,
+            // subgroups don't fit the `qual/name` paths that repositories are analyzed under
+            Err(err) if err.is::<webhook::NestedNamespace>() => {
+                info!(logger, "ignored webhook: {}", err);
+                return Ok(plain_text(StatusCode::OK, "ignored"));
+            }
+            Err(err) => {
+                error!(logger, "invalid webhook payload: {}", err);
+                return Ok(plain_text(StatusCode::BAD_REQUEST, "invalid push payload"));
+            }
+        };
+
+        for repo_path in push.affected_paths() {
+            let engine = self.engine.clone();
+            let logger = logger.clone();
+            tokio::spawn(async move {
+                if let Err(err) = engine.reanalyze_repo_dependencies(repo_path.clone()).await {
+                    error!(logger, "failed to reanalyze {}: {}", repo_path, err);
+                }
+            });
+        }
+
+        Ok(plain_text(StatusCode::ACCEPTED, "accepted"))
+    }
+
     async fn crate_redirect(
         &self,
         _req: Request<Body>,
@@ -280,21 +529,14 @@ impl App {
 
     async fn crate_status(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
         params: Params,
         logger: Logger,
         format: StatusFormat,
     ) -> Result<Response<Body>, HyperError> {
         let server = self.clone();
 
-        let name = params.find("name").expect("route param 'name' not found");
-        let version = params
-            .find("version")
-            .expect("route param 'version' not found");
-
-        let crate_path_result = CratePath::from_parts(name, version);
-
-        match crate_path_result {
+        match parse_crate_path(&params) {
             Err(err) => {
                 error!(logger, "error: {}", err);
                 let mut response = views::html::error::render(
@@ -305,42 +547,60 @@ impl App {
                 Ok(response)
             }
             Ok(crate_path) => {
-                let analyze_result = server
-                    .engine
-                    .analyze_crate_dependencies(crate_path.clone())
-                    .await;
+                let query = StatusQuery::from_request(&req);
 
-                match analyze_result {
-                    Err(err) => {
-                        error!(logger, "error: {}", err);
-                        let response = App::status_format_analysis(
-                            None,
-                            format,
-                            SubjectPath::Crate(crate_path),
-                        );
-                        Ok(response)
-                    }
-                    Ok(analysis_outcome) => {
-                        let response = App::status_format_analysis(
-                            Some(analysis_outcome),
-                            format,
-                            SubjectPath::Crate(crate_path),
-                        );
+                let analyze_result = if query.transitive {
+                    server
+                        .engine
+                        .analyze_crate_dependency_tree(crate_path.clone())
+                        .await
+                } else {
+                    server
+                        .engine
+                        .analyze_crate_dependencies(crate_path.clone())
+                        .await
+                };
 
-                        Ok(response)
-                    }
+                if let Err(ref err) = analyze_result {
+                    error!(logger, "error: {}", err);
                 }
+
+                let response = App::status_format_analysis(
+                    analyze_result.as_deref(),
+                    format,
+                    SubjectPath::Crate(crate_path),
+                    &query,
+                );
+                Ok(response)
             }
         }
     }
 
     fn status_format_analysis(
-        analysis_outcome: Option<AnalyzeDependenciesOutcome>,
+        analysis_outcome: Result<&AnalyzeDependenciesOutcome, &AnalysisFailure>,
         format: StatusFormat,
         subject_path: SubjectPath,
+        query: &StatusQuery,
     ) -> Response<Body> {
         match format {
-            StatusFormat::Svg => views::badge::response(analysis_outcome.as_ref()),
+            StatusFormat::Svg => views::badge::response(
+                analysis_outcome.map_err(|failure| failure.kind),
+                query.badge_kind(),
+                &query.badge_appearance(),
+            ),
+            StatusFormat::Png => views::badge::png_response(
+                analysis_outcome.map_err(|failure| failure.kind),
+                query.badge_kind(),
+                &query.badge_appearance(),
+            ),
+            StatusFormat::Json => {
+                views::json::response(analysis_outcome.map_err(|failure| failure.kind))
+            }
+            StatusFormat::Shields => views::badge::shields_response(
+                analysis_outcome.map_err(|failure| failure.kind),
+                query.badge_kind(),
+                query.subject.as_deref(),
+            ),
             StatusFormat::Html => views::html::status::render(analysis_outcome, subject_path),
         }
     }
@@ -361,9 +621,64 @@ impl App {
     }
 }
 
+/// Reads the repository path from the route params, and the ref from the query string
+fn parse_repo_path(req: &Request<Body>, params: &Params) -> Result<RepoPath, Error> {
+    let site = params.find("site").expect("route param 'site' not found");
+    let qual = params.find("qual").expect("route param 'qual' not found");
+    let name = params.find("name").expect("route param 'name' not found");
+
+    let query = StatusQuery::from_request(req);
+    let git_ref = query
+        .git_ref
+        .as_deref()
+        .map(str::parse::<GitRef>)
+        .transpose()?;
+
+    Ok(RepoPath::from_parts(site, qual, name)?.with_git_ref(git_ref))
+}
+
+fn parse_crate_path(params: &Params) -> Result<CratePath, Error> {
+    let name = params.find("name").expect("route param 'name' not found");
+    let version = params
+        .find("version")
+        .expect("route param 'version' not found");
+    let registry = params.find("registry").map(str::parse).transpose()?;
+
+    Ok(CratePath::from_parts(name, version)?.with_registry(registry))
+}
+
+fn plain_text(status: StatusCode, body: &'static str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
 fn not_found() -> Response<Body> {
     views::html::error::render_404()
 }
 
-static SELF_BASE_URL: Lazy<String> =
+/// Upper bound for webhook payloads, which GitHub caps at 25 MB
+const MAX_WEBHOOK_PAYLOAD: usize = 25 * 1024 * 1024;
+
+pub(crate) static SELF_BASE_URL: Lazy<String> =
     Lazy::new(|| env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_valid_query_parameters() {
+        let req =
+            Request::get("/repo/github/deps-rs/deps.rs?ref=develop&transitive=yes&style=flat")
+                .body(Body::empty())
+                .unwrap();
+
+        let query = StatusQuery::from_request(&req);
+        assert_eq!(query.git_ref.as_deref(), Some("develop"));
+        assert!(!query.transitive);
+        assert_eq!(query.style.as_deref(), Some("flat"));
+    }
+}
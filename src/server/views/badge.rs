@@ -1,57 +1,331 @@
-use badge::{Badge, BadgeOptions};
+use badge::{Badge, BadgeOptions, BadgeStyle};
 use hyper::header::CONTENT_TYPE;
 use hyper::{Body, Response};
+use once_cell::sync::Lazy;
+use serde::Serialize;
 
-use crate::engine::AnalyzeDependenciesOutcome;
-
-pub fn badge(analysis_outcome: Option<&AnalyzeDependenciesOutcome>) -> Badge {
-    let opts = match analysis_outcome {
-        Some(outcome) => {
-            if outcome.any_insecure() {
-                BadgeOptions {
-                    subject: "dependencies".into(),
-                    status: "insecure".into(),
-                    color: "#e05d44".into(),
-                }
-            } else {
-                let (outdated, total) = outcome.outdated_ratio();
+use crate::engine::{AnalyzeDependenciesOutcome, FailureKind};
+use crate::server::assets;
 
-                if outdated > 0 {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: format!("{} of {} outdated", outdated, total),
-                        color: "#dfb317".into(),
-                    }
-                } else if total > 0 {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: "up to date".into(),
-                        color: "#4c1".into(),
-                    }
-                } else {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: "none".into(),
-                        color: "#4c1".into(),
-                    }
-                }
-            }
+/// Upper bound for the length of custom logos, which are embedded in every badge
+const MAX_LOGO_LEN: usize = 16 * 1024;
+/// Upper bound for the number of characters of custom subjects, which determine the badge width
+const MAX_SUBJECT_CHARS: usize = 64;
+
+static DEPS_RS_LOGO: Lazy<String> = Lazy::new(|| {
+    format!(
+        "data:image/svg+xml;base64,{}",
+        base64::encode(assets::STATIC_FAVICON)
+    )
+});
+
+/// What a badge judges the dependencies by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BadgeKind {
+    /// Insecure dependencies take priority over outdated ones
+    #[default]
+    Status,
+    /// Only the number of advisories
+    Security,
+    /// Only outdated dependencies
+    Outdated,
+    /// Like `Status`, but dev-dependencies count as well
+    All,
+}
+
+impl BadgeKind {
+    /// Reads the kind from a query parameter, falling back to the default if it is unknown
+    pub fn from_query(kind: Option<&str>) -> BadgeKind {
+        match kind {
+            Some("security") => BadgeKind::Security,
+            Some("outdated") => BadgeKind::Outdated,
+            Some("all") => BadgeKind::All,
+            _ => BadgeKind::Status,
         }
-        None => BadgeOptions {
-            subject: "dependencies".into(),
-            status: "unknown".into(),
-            color: "#9f9f9f".into(),
+    }
+}
+
+/// How a badge is presented, independently of what it shows
+#[derive(Debug, Default)]
+pub struct BadgeAppearance {
+    pub subject: Option<String>,
+    pub style: BadgeStyle,
+    /// Data URI of the logo in front of the subject
+    pub logo: Option<String>,
+}
+
+impl BadgeAppearance {
+    /// Reads the appearance from query parameters, ignoring values that are not understood.
+    /// Subjects are truncated, and logos are either `deps.rs` or a base64 encoded PNG or SVG
+    /// data URI.
+    pub fn from_query(style: Option<&str>, subject: Option<&str>, logo: Option<&str>) -> Self {
+        BadgeAppearance {
+            subject: subject.map(|subject| subject.chars().take(MAX_SUBJECT_CHARS).collect()),
+            style: style
+                .and_then(|style| style.parse().ok())
+                .unwrap_or_default(),
+            logo: logo.and_then(|logo| match logo {
+                "deps.rs" => Some(DEPS_RS_LOGO.clone()),
+                logo if is_image_data_uri(logo) => Some(logo.to_string()),
+                _ => None,
+            }),
+        }
+    }
+}
+
+fn is_image_data_uri(uri: &str) -> bool {
+    let data = match uri
+        .strip_prefix("data:image/svg+xml;base64,")
+        .or_else(|| uri.strip_prefix("data:image/png;base64,"))
+    {
+        Some(data) => data,
+        None => return false,
+    };
+
+    uri.len() <= MAX_LOGO_LEN
+        && data
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=')
+}
+
+fn outdated_status(outdated: usize, total: usize) -> (String, &'static str) {
+    if outdated > 0 {
+        (format!("{} of {} outdated", outdated, total), "#dfb317")
+    } else if total > 0 {
+        ("up to date".to_string(), "#4c1")
+    } else {
+        ("none".to_string(), "#4c1")
+    }
+}
+
+/// What a badge says, independently of how it is drawn
+#[derive(Debug)]
+pub struct BadgeContent {
+    pub subject: String,
+    pub status: String,
+    /// Hex color of the status, e.g. `#4c1`
+    pub color: &'static str,
+}
+
+pub fn content(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+    kind: BadgeKind,
+    subject: Option<&str>,
+) -> BadgeContent {
+    let (status, color) = match analysis_outcome {
+        Ok(outcome) => match kind {
+            BadgeKind::Status if outcome.any_insecure() => ("insecure".to_string(), "#e05d44"),
+            BadgeKind::Status => {
+                let (outdated, total) = outcome.outdated_ratio();
+                outdated_status(outdated, total)
+            }
+            BadgeKind::Security => match outcome.count_advisories() {
+                0 => ("no advisories".to_string(), "#4c1"),
+                1 => ("1 advisory".to_string(), "#e05d44"),
+                count => (format!("{} advisories", count), "#e05d44"),
+            },
+            BadgeKind::Outdated => {
+                let (outdated, total) = outcome.outdated_ratio();
+                outdated_status(outdated, total)
+            }
+            BadgeKind::All if outcome.any_insecure() || outcome.count_dev_insecure() > 0 => {
+                ("insecure".to_string(), "#e05d44")
+            }
+            BadgeKind::All => {
+                let (outdated, total) = outcome.outdated_ratio_with_dev();
+                outdated_status(outdated, total)
+            }
         },
+        Err(kind) => (kind.to_string(), "#9f9f9f"),
+    };
+
+    let default_subject = match kind {
+        BadgeKind::Security => "security",
+        _ => "dependencies",
     };
 
-    Badge::new(opts)
+    BadgeContent {
+        subject: subject.unwrap_or(default_subject).to_string(),
+        status,
+        color,
+    }
 }
 
-pub fn response(analysis_outcome: Option<&AnalyzeDependenciesOutcome>) -> Response<Body> {
-    let badge = badge(analysis_outcome).to_svg();
+pub fn badge(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+    kind: BadgeKind,
+    appearance: &BadgeAppearance,
+) -> Badge {
+    let content = content(analysis_outcome, kind, appearance.subject.as_deref());
+
+    Badge::new(BadgeOptions {
+        subject: content.subject,
+        status: content.status,
+        color: content.color.into(),
+        style: appearance.style,
+        logo: appearance.logo.clone(),
+    })
+}
+
+pub fn response(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+    kind: BadgeKind,
+    appearance: &BadgeAppearance,
+) -> Response<Body> {
+    let badge = badge(analysis_outcome, kind, appearance).to_svg();
 
     Response::builder()
         .header(CONTENT_TYPE, "image/svg+xml; charset=utf-8")
         .body(Body::from(badge))
         .unwrap()
 }
+
+/// Like `response`, for places that can't embed SVG images
+pub fn png_response(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+    kind: BadgeKind,
+    appearance: &BadgeAppearance,
+) -> Response<Body> {
+    let badge = badge(analysis_outcome, kind, appearance).to_png();
+
+    Response::builder()
+        .header(CONTENT_TYPE, "image/png")
+        .body(Body::from(badge))
+        .unwrap()
+}
+
+/// The endpoint schema of shields.io, see https://shields.io/endpoint
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShieldsEndpoint {
+    schema_version: u8,
+    label: String,
+    message: String,
+    color: String,
+    is_error: bool,
+}
+
+/// Serves the badge content for shields.io to draw. Failures still respond with `200 OK`,
+/// since shields.io only draws badges for successful responses.
+pub fn shields_response(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+    kind: BadgeKind,
+    subject: Option<&str>,
+) -> Response<Body> {
+    let is_error = analysis_outcome.is_err();
+    let content = content(analysis_outcome, kind, subject);
+
+    let endpoint = ShieldsEndpoint {
+        schema_version: 1,
+        label: content.subject,
+        message: content.status,
+        // shields.io takes hex colors without the leading `#`
+        color: content.color.trim_start_matches('#').to_string(),
+        is_error,
+    };
+    let body = serde_json::to_vec(&endpoint).expect("failed to serialize shields.io endpoint");
+
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::models::crates::{
+        test_advisory, AnalyzedDependencies, AnalyzedDependency, CrateDeps,
+    };
+
+    /// An outcome with an outdated normal dependency and an insecure dev-dependency
+    fn outcome() -> AnalyzeDependenciesOutcome {
+        let mut deps = AnalyzedDependencies::new(&CrateDeps::default());
+
+        let mut serde = AnalyzedDependency::new("^0.9".parse().unwrap());
+        serde.latest_that_matches = Some("0.9.15".parse().unwrap());
+        serde.latest = Some("1.0.130".parse().unwrap());
+        deps.main.insert("serde".parse().unwrap(), serde);
+
+        let mut hyper = AnalyzedDependency::new("^0.14".parse().unwrap());
+        hyper.latest_that_matches = Some("0.14.2".parse().unwrap());
+        hyper.latest = Some("0.14.2".parse().unwrap());
+        hyper.vulnerabilities.push(test_advisory());
+        deps.dev.insert("hyper".parse().unwrap(), hyper);
+
+        AnalyzeDependenciesOutcome {
+            crates: vec![("app".parse().unwrap(), deps)],
+            lockfile: None,
+            tree: None,
+            duration: Duration::from_secs(0),
+        }
+    }
+
+    #[test]
+    fn badge_kinds_judge_differently() {
+        let outcome = outcome();
+        let svg = |kind| badge(Ok(&outcome), kind, &BadgeAppearance::default()).to_svg();
+
+        assert!(svg(BadgeKind::Status).contains(">1 of 1 outdated<"));
+        assert!(svg(BadgeKind::Outdated).contains(">1 of 1 outdated<"));
+        let security = svg(BadgeKind::Security);
+        assert!(security.contains(">security<"));
+        assert!(security.contains(">no advisories<"));
+        assert!(svg(BadgeKind::All).contains(">insecure<"));
+
+        assert_eq!(BadgeKind::from_query(Some("security")), BadgeKind::Security);
+        assert_eq!(BadgeKind::from_query(Some("nope")), BadgeKind::Status);
+    }
+
+    #[tokio::test]
+    async fn serves_shields_endpoint() {
+        let outcome = outcome();
+
+        let response = shields_response(Ok(&outcome), BadgeKind::Security, None);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let endpoint: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            endpoint,
+            serde_json::json!({
+                "schemaVersion": 1,
+                "label": "security",
+                "message": "no advisories",
+                "color": "4c1",
+                "isError": false,
+            })
+        );
+
+        let response =
+            shields_response(Err(FailureKind::NotFound), BadgeKind::Status, Some("deps"));
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let endpoint: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(endpoint["label"], "deps");
+        assert_eq!(endpoint["isError"], true);
+    }
+
+    #[test]
+    fn reads_appearance_from_query() {
+        let appearance =
+            BadgeAppearance::from_query(Some("for-the-badge"), Some("deps"), Some("deps.rs"));
+        assert_eq!(appearance.style, BadgeStyle::ForTheBadge);
+        assert_eq!(appearance.subject.as_deref(), Some("deps"));
+        assert!(appearance
+            .logo
+            .unwrap()
+            .starts_with("data:image/svg+xml;base64,"));
+
+        let appearance = BadgeAppearance::from_query(Some("round"), None, Some("https://x.test"));
+        assert_eq!(appearance.style, BadgeStyle::Flat);
+        assert!(appearance.logo.is_none());
+
+        let long_subject = "x".repeat(64 * 1024);
+        let appearance = BadgeAppearance::from_query(None, Some(&long_subject), None);
+        assert_eq!(appearance.subject.unwrap().len(), MAX_SUBJECT_CHARS);
+
+        assert!(is_image_data_uri("data:image/png;base64,iVBORw0KGgo="));
+        assert!(!is_image_data_uri("data:image/png;base64,\"><script>"));
+    }
+}
@@ -0,0 +1,100 @@
+use hyper::{Body, Response};
+use maud::{html, Markup};
+
+use crate::models::SubjectPath;
+use crate::utils::history::HistoryEntry;
+
+fn render_entry(entry: &HistoryEntry) -> Markup {
+    html! {
+        tr {
+            td { (entry.analyzed_at) }
+            td class="has-text-right" {
+                @if entry.outdated > 0 {
+                    span class="tag is-warning" { (entry.outdated) }
+                } @else {
+                    span class="tag is-success" { "0" }
+                }
+            }
+            td class="has-text-right" { (entry.total) }
+            td class="has-text-right" {
+                @if entry.insecure > 0 {
+                    span class="tag is-danger" { (entry.insecure) }
+                } @else {
+                    span class="tag is-success" { "0" }
+                }
+            }
+        }
+    }
+}
+
+pub fn render(subject_path: &SubjectPath, entries: Vec<HistoryEntry>) -> Response<Body> {
+    let status_path = format!("/{}", subject_path.path());
+    let (name, status_url, tag) = match subject_path {
+        SubjectPath::Repo(repo_path) => {
+            let name = format!("{} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref());
+            match repo_path.git_ref {
+                Some(ref git_ref) => (
+                    name,
+                    format!("{}?ref={}", status_path, git_ref.as_ref()),
+                    Some(git_ref.as_ref()),
+                ),
+                None => (name, status_path, None),
+            }
+        }
+        SubjectPath::Crate(crate_path) => (
+            format!("{} {}", crate_path.name.as_ref(), crate_path.version),
+            status_path,
+            crate_path
+                .registry
+                .as_ref()
+                .map(|registry| registry.as_ref()),
+        ),
+    };
+
+    let title = format!("History of {}", name);
+
+    super::render_html(
+        &title,
+        html! {
+            section class="hero is-light" {
+                div class="hero-head" { (super::render_navbar()) }
+                div class="hero-body" {
+                    div class="container" {
+                        h1 class="title is-1" {
+                            a href=(status_url) { (name) }
+                            @if let Some(ref tag) = tag {
+                                " "
+                                span class="tag is-medium" { code { (tag) } }
+                            }
+                        }
+                        h2 class="subtitle" { "Analysis history" }
+                    }
+                }
+            }
+            section class="section" {
+                div class="container" {
+                    @if entries.is_empty() {
+                        p class="has-text-centered" { "This has not been analyzed yet." }
+                    } @else {
+                        table class="table is-fullwidth is-striped is-hoverable" {
+                            thead {
+                                tr {
+                                    th { "Analyzed at (UTC)" }
+                                    th class="has-text-right" { "Outdated" }
+                                    th class="has-text-right" { "Total" }
+                                    th class="has-text-right" { "Insecure" }
+                                }
+                            }
+                            tbody {
+                                @for entry in &entries {
+                                    (render_entry(entry))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            (super::render_footer(None))
+        },
+    )
+}
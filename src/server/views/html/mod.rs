@@ -5,6 +5,7 @@ use hyper::{Body, Response};
 use maud::{html, Markup, Render};
 
 pub mod error;
+pub mod history;
 pub mod index;
 pub mod status;
 
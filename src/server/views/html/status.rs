@@ -6,11 +6,14 @@ use pulldown_cmark::{html, Parser};
 use rustsec::advisory::Advisory;
 use semver::Version;
 
-use crate::engine::AnalyzeDependenciesOutcome;
-use crate::models::crates::{AnalyzedDependencies, AnalyzedDependency, CrateName};
-use crate::models::repo::RepoSite;
+use crate::engine::{AnalysisFailure, AnalyzeDependenciesOutcome, FailureKind};
+use crate::models::crates::{
+    AnalyzedDependencies, AnalyzedDependency, AnalyzedDependencyTree, AnalyzedGitDependency,
+    AnalyzedLockfile, CrateName,
+};
+use crate::models::repo::{CustomSiteKind, RepoSite};
 use crate::models::SubjectPath;
-use crate::server::views::badge;
+use crate::server::views::badge::{self, BadgeKind};
 
 fn get_crates_url(name: impl AsRef<str>) -> String {
     format!("https://crates.io/crates/{}", name.as_ref())
@@ -27,7 +30,7 @@ fn dependency_tables(crate_name: &CrateName, deps: &AnalyzedDependencies) -> Mar
             code { (crate_name.as_ref()) }
         }
 
-        @if deps.main.is_empty() && deps.dev.is_empty() && deps.build.is_empty() {
+        @if deps.main.is_empty() && deps.dev.is_empty() && deps.build.is_empty() && deps.git.is_empty() && deps.ignored.is_empty() {
             p class="notification has-text-centered" { "No external dependencies! 🙌" }
         }
 
@@ -42,13 +45,84 @@ fn dependency_tables(crate_name: &CrateName, deps: &AnalyzedDependencies) -> Mar
         @if !deps.build.is_empty() {
             (dependency_table("Build dependencies", &deps.build))
         }
+
+        @if !deps.git.is_empty() {
+            (git_dependency_table(&deps.git))
+        }
     }
 }
 
-fn dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>) -> Markup {
+fn git_dependency_table(deps: &IndexMap<CrateName, AnalyzedGitDependency>) -> Markup {
     let count_total = deps.len();
-    let count_insecure = deps.iter().filter(|&(_, dep)| dep.is_insecure()).count();
-    let count_outdated = deps.iter().filter(|&(_, dep)| dep.is_outdated()).count();
+    let count_published = deps.values().filter(|dep| dep.is_published()).count();
+
+    let fa_cube = PreEscaped(fa(FaType::Solid, "cube").unwrap());
+
+    html! {
+        h3 class="title is-4" { "Git dependencies" }
+        p class="subtitle is-5" {
+            (format!("({} total, {} published on crates.io)", count_total, count_published))
+        }
+
+        table class="table is-fullwidth is-striped is-hoverable" {
+            thead {
+                tr {
+                    th { "Crate" }
+                    th { "Repository" }
+                    th class="has-text-right" { "Pinned to" }
+                    th class="has-text-right" { "Latest on crates.io" }
+                }
+            }
+            tbody {
+                @for (name, dep) in deps {
+                    tr {
+                        td {
+                            @if dep.is_published() {
+                                a class="has-text-grey" href=(get_crates_url(name)) {
+                                    { (fa_cube) }
+                                }
+                                { "\u{00A0}" } // non-breaking space
+                            }
+                            (name.as_ref())
+                        }
+                        td {
+                            // the URL comes from an untrusted manifest, so only web links are emitted
+                            @if let Some(web_url) = dep.git.web_url() {
+                                a href=(web_url) { (dep.git.url) }
+                            } @else {
+                                code { (dep.git.url) }
+                            }
+                        }
+                        td class="has-text-right" { code { (dep.git.pin.to_string()) } }
+                        td class="has-text-right" {
+                            @if let Some(ref latest) = dep.latest {
+                                code { (latest.to_string()) }
+                            } @else {
+                                "unpublished"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>) -> Markup {
+    // declarations for other platforms are listed on rows of their own
+    let declarations: Vec<_> = deps
+        .iter()
+        .flat_map(|(name, dep)| dep.declarations().map(move |dep| (name, dep)))
+        .collect();
+    let count_total = declarations.len();
+    let count_insecure = declarations
+        .iter()
+        .filter(|(_, dep)| dep.is_insecure())
+        .count();
+    let count_outdated = declarations
+        .iter()
+        .filter(|(_, dep)| dep.is_outdated())
+        .count();
 
     let fa_cube = PreEscaped(fa(FaType::Solid, "cube").unwrap());
 
@@ -73,14 +147,28 @@ fn dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>)
                 }
             }
             tbody {
-                @for (name, dep) in deps {
+                @for (name, dep) in declarations {
                     tr {
                         td {
-                            a class="has-text-grey" href=(get_crates_url(&name)) {
-                                { (fa_cube) }
+                            @if dep.registry.is_none() {
+                                a class="has-text-grey" href=(get_crates_url(&name)) {
+                                    { (fa_cube) }
+                                }
+                                { "\u{00A0}" } // non-breaking space
                             }
-                            { "\u{00A0}" } // non-breaking space
                             a href=(dep.deps_rs_path(name.as_ref())) { (name.as_ref()) }
+                            @if let Some(ref registry) = dep.registry {
+                                " "
+                                span class="tag is-info is-light" title="Alternate registry" { (registry.as_ref()) }
+                            }
+                            @if dep.optional {
+                                " "
+                                span class="tag is-light" { "optional" }
+                            }
+                            @if let Some(ref target) = dep.target {
+                                " "
+                                span class="tag is-light" title="Platform-specific dependency" { code { (target) } }
+                            }
                         }
                         td class="has-text-right" { code { (dep.required.to_string()) } }
                         td class="has-text-right" {
@@ -95,6 +183,8 @@ fn dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>)
                                 span class="tag is-danger" { "insecure" }
                             } @else if dep.is_outdated() {
                                 span class="tag is-warning" { "out of date" }
+                            } @else if dep.pinned && dep.is_behind() {
+                                span class="tag is-info" title="Held back on purpose" { "pinned" }
                             } @else {
                                 span class="tag is-success" { "up to date" }
                             }
@@ -106,11 +196,148 @@ fn dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>)
     }
 }
 
+fn lockfile_table(lockfile: &AnalyzedLockfile) -> Markup {
+    let count_total = lockfile.packages.len();
+    let count_insecure = lockfile.count_insecure();
+    let count_yanked = lockfile.count_yanked();
+
+    let fa_cube = PreEscaped(fa(FaType::Solid, "cube").unwrap());
+
+    html! {
+        h2 class="title is-3" {
+            "Lockfile "
+            code { "Cargo.lock" }
+        }
+        p class="subtitle is-5" {
+            (match (count_yanked, count_insecure) {
+                (0, 0) => format!("({} locked packages, none insecure or yanked)", count_total),
+                (0, _) => format!("({} locked packages, {} insecure)", count_total, count_insecure),
+                (_, 0) => format!("({} locked packages, {} yanked)", count_total, count_yanked),
+                (_, _) => format!("({} locked packages, {} yanked, {} insecure)", count_total, count_yanked, count_insecure),
+            })
+        }
+
+        details open[count_insecure > 0 || count_yanked > 0] {
+            summary { "Show locked packages" }
+
+            table class="table is-fullwidth is-striped is-hoverable" {
+                thead {
+                    tr {
+                        th { "Crate" }
+                        th class="has-text-right" { "Locked" }
+                        th class="has-text-right" { "Status" }
+                    }
+                }
+                tbody {
+                    @for pkg in &lockfile.packages {
+                        tr {
+                            td {
+                                a class="has-text-grey" href=(get_crates_url(&pkg.name)) {
+                                    { (fa_cube) }
+                                }
+                                { "\u{00A0}" } // non-breaking space
+                                a href=(get_crates_version_url(&pkg.name, &pkg.version)) { (pkg.name.as_ref()) }
+                            }
+                            td class="has-text-right" { code { (pkg.version.to_string()) } }
+                            td class="has-text-right" {
+                                @if pkg.is_insecure() {
+                                    span class="tag is-danger" { "insecure" }
+                                } @else if pkg.yanked {
+                                    span class="tag is-warning" { "yanked" }
+                                } @else {
+                                    span class="tag is-success" { "ok" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn dependency_tree_table(tree: &AnalyzedDependencyTree) -> Markup {
+    let count_total = tree.dependencies.len();
+    let count_insecure = tree.count_insecure();
+    let count_outdated = tree.count_outdated();
+
+    let fa_cube = PreEscaped(fa(FaType::Solid, "cube").unwrap());
+
+    html! {
+        h2 class="title is-3" { "Dependency tree" }
+        p class="subtitle is-5" {
+            (match (count_outdated, count_insecure) {
+                (0, 0) => format!("({} crates in total, all up-to-date)", count_total),
+                (0, _) => format!("({} crates in total, {} insecure)", count_total, count_insecure),
+                (_, 0) => format!("({} crates in total, {} outdated)", count_total, count_outdated),
+                (_, _) => format!("({} crates in total, {} outdated, {} insecure)", count_total, count_outdated, count_insecure),
+            })
+        }
+
+        @if count_outdated > 0 || count_insecure > 0 {
+            table class="table is-fullwidth is-striped is-hoverable" {
+                thead {
+                    tr {
+                        th { "Crate" }
+                        th { "Required by" }
+                        th class="has-text-right" { "Resolved" }
+                        th class="has-text-right" { "Latest" }
+                        th class="has-text-right" { "Status" }
+                    }
+                }
+                tbody {
+                    @for dep in tree.dependencies.iter().filter(|dep| dep.analyzed.is_insecure() || dep.analyzed.is_outdated()) {
+                        tr {
+                            td {
+                                a class="has-text-grey" href=(get_crates_url(&dep.name)) {
+                                    { (fa_cube) }
+                                }
+                                { "\u{00A0}" } // non-breaking space
+                                a href=(dep.analyzed.deps_rs_path(dep.name.as_ref())) { (dep.name.as_ref()) }
+                            }
+                            td {
+                                @for (idx, parent) in dep.path.iter().enumerate() {
+                                    @if idx > 0 { " → " }
+                                    code { (parent.as_ref()) }
+                                }
+                            }
+                            td class="has-text-right" {
+                                @if let Some(ref resolved) = dep.analyzed.latest_that_matches {
+                                    code { (resolved.to_string()) }
+                                }
+                            }
+                            td class="has-text-right" {
+                                @if let Some(ref latest) = dep.analyzed.latest {
+                                    code { (latest.to_string()) }
+                                } @else {
+                                    "N/A"
+                                }
+                            }
+                            td class="has-text-right" {
+                                @if dep.analyzed.is_insecure() {
+                                    span class="tag is-danger" { "insecure" }
+                                } @else {
+                                    span class="tag is-warning" { "out of date" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn get_site_icon(site: &RepoSite) -> &'static str {
     match *site {
         RepoSite::Github => "github",
         RepoSite::Gitlab => "gitlab",
         RepoSite::Bitbucket => "bitbucket",
+        RepoSite::Custom(site) => match site.kind {
+            CustomSiteKind::Gitlab => "gitlab",
+            CustomSiteKind::Gitea => "git-alt",
+            CustomSiteKind::GithubEnterprise => "github-alt",
+        },
     }
 }
 
@@ -121,20 +348,32 @@ fn render_title(subject_path: &SubjectPath) -> Markup {
             let fa_site_icon = PreEscaped(fa(FaType::Brands, site_icon).unwrap());
 
             html! {
-                a href=(format!("{}/{}/{}", repo_path.site.to_base_uri(), repo_path.qual.as_ref(), repo_path.name.as_ref())) {
+                a href=(repo_path.to_web_url()) {
                     { (fa_site_icon) }
                     (format!(" {} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref()))
                 }
+                @if let Some(ref git_ref) = repo_path.git_ref {
+                    " "
+                    span class="tag is-medium" { code { (git_ref.as_ref()) } }
+                }
             }
         }
         SubjectPath::Crate(ref crate_path) => {
             let fa_cube = PreEscaped(fa(FaType::Solid, "cube").unwrap());
 
-            html! {
-                a href=(get_crates_version_url(&crate_path.name, &crate_path.version)) {
+            match crate_path.registry {
+                Some(ref registry) => html! {
                     { (fa_cube) }
                     (format!(" {} {}", crate_path.name.as_ref(), crate_path.version))
-                }
+                    " "
+                    span class="tag is-medium" { (registry.as_ref()) }
+                },
+                None => html! {
+                    a href=(get_crates_version_url(&crate_path.name, &crate_path.version)) {
+                        { (fa_cube) }
+                        (format!(" {} {}", crate_path.name.as_ref(), crate_path.version))
+                    }
+                },
             }
         }
     }
@@ -171,35 +410,7 @@ fn render_markdown(description: &str) -> Markup {
 
 /// Renders a list of all security vulnerabilities affecting the repository
 fn vulnerability_list(analysis_outcome: &AnalyzeDependenciesOutcome) -> Markup {
-    let mut vulnerabilities = Vec::new();
-    for (_, analyzed_crate) in &analysis_outcome.crates {
-        vulnerabilities.extend(
-            &mut analyzed_crate
-                .main
-                .iter()
-                .filter(|&(_, dep)| dep.is_insecure())
-                .map(|(_, dep)| &dep.vulnerabilities),
-        );
-        vulnerabilities.extend(
-            &mut analyzed_crate
-                .dev
-                .iter()
-                .filter(|&(_, dep)| dep.is_insecure())
-                .map(|(_, dep)| &dep.vulnerabilities),
-        );
-        vulnerabilities.extend(
-            &mut analyzed_crate
-                .build
-                .iter()
-                .filter(|&(_, dep)| dep.is_insecure())
-                .map(|(_, dep)| &dep.vulnerabilities),
-        );
-    }
-
-    // flatten Vec<Vec<&Advisory>> -> Vec<&Advisory>
-    let mut vulnerabilities: Vec<&Advisory> = vulnerabilities.into_iter().flatten().collect();
-    vulnerabilities.sort_unstable_by_key(|&v| v.id());
-    vulnerabilities.dedup();
+    let vulnerabilities = analysis_outcome.vulnerabilities();
 
     html! {
         h3 class="title is-3" id="vulnerabilities" { "Security Vulnerabilities" }
@@ -242,7 +453,115 @@ fn vulnerability_list(analysis_outcome: &AnalyzeDependenciesOutcome) -> Markup {
     }
 }
 
-fn render_failure(subject_path: SubjectPath) -> Markup {
+/// Renders what the repository's policy keeps from counting towards the status
+fn ignored_list(analysis_outcome: &AnalyzeDependenciesOutcome) -> Markup {
+    let ignored_vulnerabilities = analysis_outcome.ignored_vulnerabilities();
+
+    html! {
+        h3 class="title is-3" id="ignored" { "Ignored" }
+        p class="subtitle is-5" {
+            "Left out of the status by the project's "
+            code { ".deps.toml" }
+            " or "
+            code { "[package.metadata.deps-rs]" }
+            " or "
+            code { "[workspace.metadata.deps-rs]" }
+        }
+
+        table class="table is-fullwidth is-striped is-hoverable" {
+            thead {
+                tr {
+                    th { "Crate" }
+                    th { "Dependency" }
+                    th class="has-text-right" { "Required" }
+                    th class="has-text-right" { "Latest" }
+                    th class="has-text-right" { "Status" }
+                }
+            }
+            tbody {
+                @for (crate_name, deps) in &analysis_outcome.crates {
+                    @for (name, dep) in deps.pinned_behind() {
+                        tr {
+                            td { code { (crate_name.as_ref()) } }
+                            td { a href=(dep.deps_rs_path(name.as_ref())) { (name.as_ref()) } }
+                            td class="has-text-right" { code { (dep.required.to_string()) } }
+                            td class="has-text-right" {
+                                @if let Some(ref latest) = dep.latest {
+                                    code { (latest.to_string()) }
+                                }
+                            }
+                            td class="has-text-right" { span class="tag is-info" { "pinned" } }
+                        }
+                    }
+                    @for name in &deps.ignored {
+                        tr {
+                            td { code { (crate_name.as_ref()) } }
+                            td { (name.as_ref()) }
+                            td {}
+                            td {}
+                            td class="has-text-right" { span class="tag is-light" { "ignored" } }
+                        }
+                    }
+                }
+                @if let Some(ref lockfile) = analysis_outcome.lockfile {
+                    @for name in &lockfile.ignored {
+                        tr {
+                            td { code { "Cargo.lock" } }
+                            td { (name.as_ref()) }
+                            td {}
+                            td {}
+                            td class="has-text-right" { span class="tag is-light" { "ignored" } }
+                        }
+                    }
+                }
+            }
+        }
+
+        @if !ignored_vulnerabilities.is_empty() {
+            h4 class="title is-4" { "Ignored advisories" }
+            ul {
+                @for vuln in ignored_vulnerabilities {
+                    li {
+                        a href=(build_rustsec_link(vuln)) { (vuln.id()) }
+                        " "
+                        code { (vuln.metadata.package.as_str()) }
+                        ": "
+                        (vuln.title())
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_failure(failure: &AnalysisFailure, subject_path: SubjectPath) -> Markup {
+    let (title, descr) = match (failure.kind, &subject_path) {
+        (FailureKind::NotFound, SubjectPath::Repo(_)) => (
+            "Repository not found",
+            "We could not find a Cargo.toml in this repository. Please make sure that the repository is public and that the requested ref exists.",
+        ),
+        (FailureKind::NotFound, SubjectPath::Crate(_)) => (
+            "Crate not found",
+            "The requested crate version could not be found in the registry.",
+        ),
+        (FailureKind::ParseError, _) => (
+            "Failed to parse the manifest",
+            "One of the manifests could not be parsed. Please check that it is a valid Cargo.toml.",
+        ),
+        (FailureKind::RateLimited, _) => (
+            "Rate limited",
+            "The code hosting site is limiting our requests at the moment. Please try again in a few minutes.",
+        ),
+        (FailureKind::Timeout, _) => (
+            "Timed out",
+            "Retrieving the project took too long. Please try again in a few minutes.",
+        ),
+        (FailureKind::Unknown, _) => (
+            "Failed to analyze repository",
+            "The repository you requested might be structured in an uncommon way that is not yet supported.",
+        ),
+    };
+
     html! {
         section class="hero is-light" {
             div class="hero-head" { (super::render_navbar()) }
@@ -257,8 +576,11 @@ fn render_failure(subject_path: SubjectPath) -> Markup {
         section class="section" {
             div class="container" {
                 div class="notification is-danger" {
-                    h2 class="title is-3" { "Failed to analyze repository" }
-                    p { "The repository you requested might be structured in an uncommon way that is not yet supported." }
+                    h2 class="title is-3" { (title) }
+                    p { (descr) }
+                    @if failure.kind == FailureKind::ParseError {
+                        pre { (format!("{:#}", failure.error())) }
+                    }
                 }
             }
         }
@@ -267,23 +589,23 @@ fn render_failure(subject_path: SubjectPath) -> Markup {
 }
 
 fn render_success(
-    analysis_outcome: AnalyzeDependenciesOutcome,
+    analysis_outcome: &AnalyzeDependenciesOutcome,
     subject_path: SubjectPath,
 ) -> Markup {
-    let self_path = match subject_path {
-        SubjectPath::Repo(ref repo_path) => format!(
-            "repo/{}/{}/{}",
-            repo_path.site.as_ref(),
-            repo_path.qual.as_ref(),
-            repo_path.name.as_ref()
-        ),
-        SubjectPath::Crate(ref crate_path) => {
-            format!("crate/{}/{}", crate_path.name.as_ref(), crate_path.version)
-        }
+    let status_base_url = format!("{}/{}", &super::SELF_BASE_URL as &str, subject_path.path());
+    let status_query = match subject_path {
+        SubjectPath::Repo(ref repo_path) => repo_path
+            .git_ref
+            .as_ref()
+            .map(|git_ref| format!("?ref={}", git_ref.as_ref()))
+            .unwrap_or_default(),
+        SubjectPath::Crate(_) if analysis_outcome.tree.is_some() => "?transitive=true".to_string(),
+        SubjectPath::Crate(_) => String::new(),
     };
-    let status_base_url = format!("{}/{}", &super::SELF_BASE_URL as &str, self_path);
 
-    let status_data_uri = badge::badge(Some(&analysis_outcome)).to_svg_data_uri();
+    let status_data_uri =
+        badge::badge(Ok(analysis_outcome), BadgeKind::Status, &Default::default())
+            .to_svg_data_uri();
 
     let hero_class = if analysis_outcome.any_insecure() {
         "is-danger"
@@ -308,7 +630,14 @@ fn render_success(
             div class="hero-footer" {
                 div class="container" {
                     pre class="is-size-7" {
-                        (format!("[![dependency status]({}/status.svg)]({})", status_base_url, status_base_url))
+                        (format!("[![dependency status]({}/status.svg{})]({}{})", status_base_url, status_query, status_base_url, status_query))
+                    }
+                    p class="is-size-7" {
+                        @if let SubjectPath::Repo(_) = subject_path {
+                            a href=(format!("{}/history{}", status_base_url, status_query)) { "View analysis history" }
+                        } @else {
+                            a href=(format!("{}/history", status_base_url)) { "View analysis history" }
+                        }
                     }
                 }
             }
@@ -324,14 +653,30 @@ fn render_success(
                         }
                     }
                 } @else if analysis_outcome.any_dev_issues() {
-                    (render_dev_dependency_box(&analysis_outcome))
+                    (render_dev_dependency_box(analysis_outcome))
                 }
                 @for (crate_name, deps) in &analysis_outcome.crates {
                     (dependency_tables(crate_name, deps))
                 }
 
+                @if let Some(ref lockfile) = analysis_outcome.lockfile {
+                    (lockfile_table(lockfile))
+                }
+
+                @if let Some(ref tree) = analysis_outcome.tree {
+                    (dependency_tree_table(tree))
+                } @else if let SubjectPath::Crate(_) = subject_path {
+                    p class="has-text-centered" {
+                        a href="?transitive=true" { "Analyze the full dependency tree" }
+                    }
+                }
+
                 @if analysis_outcome.any_insecure() {
-                    (vulnerability_list(&analysis_outcome))
+                    (vulnerability_list(analysis_outcome))
+                }
+
+                @if analysis_outcome.any_ignored() {
+                    (ignored_list(analysis_outcome))
                 }
             }
         }
@@ -340,21 +685,63 @@ fn render_success(
 }
 
 pub fn render(
-    analysis_outcome: Option<AnalyzeDependenciesOutcome>,
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, &AnalysisFailure>,
     subject_path: SubjectPath,
 ) -> Response<Body> {
     let title = match subject_path {
-        SubjectPath::Repo(ref repo_path) => {
-            format!("{} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref())
-        }
+        SubjectPath::Repo(ref repo_path) => match repo_path.git_ref {
+            Some(ref git_ref) => format!(
+                "{} / {} @ {}",
+                repo_path.qual.as_ref(),
+                repo_path.name.as_ref(),
+                git_ref.as_ref()
+            ),
+            None => format!("{} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref()),
+        },
         SubjectPath::Crate(ref crate_path) => {
             format!("{} {}", crate_path.name.as_ref(), crate_path.version)
         }
     };
 
-    if let Some(outcome) = analysis_outcome {
-        super::render_html(&title, render_success(outcome, subject_path))
-    } else {
-        super::render_html(&title, render_failure(subject_path))
+    match analysis_outcome {
+        Ok(outcome) => super::render_html(&title, render_success(outcome, subject_path)),
+        Err(failure) => super::render_html(&title, render_failure(failure, subject_path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::crates::{GitDep, GitPin};
+
+    use super::*;
+
+    fn git_dependency(url: &str) -> IndexMap<CrateName, AnalyzedGitDependency> {
+        let mut deps = IndexMap::new();
+        deps.insert(
+            "hyper".parse().unwrap(),
+            AnalyzedGitDependency {
+                git: GitDep {
+                    url: url.to_string(),
+                    pin: GitPin::DefaultBranch,
+                },
+                latest: None,
+            },
+        );
+        deps
+    }
+
+    #[test]
+    fn links_only_web_urls_of_git_dependencies() {
+        let html = git_dependency_table(&git_dependency("javascript:alert(1)")).into_string();
+        assert!(!html.contains("href=\"javascript:"));
+        assert!(html.contains("<code>javascript:alert(1)</code>"));
+
+        let html = git_dependency_table(&git_dependency("https://github.com/hyperium/hyper"))
+            .into_string();
+        assert!(html.contains("href=\"https://github.com/hyperium/hyper\""));
+
+        let html = git_dependency_table(&git_dependency("ssh://git@github.com/hyperium/hyper.git"))
+            .into_string();
+        assert!(html.contains("href=\"https://github.com/hyperium/hyper\""));
     }
 }
@@ -0,0 +1,255 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Response, StatusCode};
+use indexmap::IndexMap;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+
+use crate::engine::{AnalyzeDependenciesOutcome, FailureKind};
+use crate::models::crates::{
+    AnalyzedDependencies, AnalyzedDependency, AnalyzedGitDependency, AnalyzedLockedPackage,
+    CrateName, GitPin, TransitiveDependency,
+};
+
+#[derive(Serialize)]
+struct JsonDependency<'a> {
+    required: &'a VersionReq,
+    target: Option<&'a str>,
+    optional: bool,
+    features: Vec<&'a str>,
+    registry: Option<&'a str>,
+    latest_that_matches: Option<&'a Version>,
+    latest: Option<&'a Version>,
+    outdated: bool,
+    insecure: bool,
+    pinned: bool,
+    advisories: Vec<&'a str>,
+    ignored_advisories: Vec<&'a str>,
+    /// Declarations for specific platforms that require something else
+    platforms: Vec<JsonDependency<'a>>,
+}
+
+impl<'a> From<&'a AnalyzedDependency> for JsonDependency<'a> {
+    fn from(dep: &'a AnalyzedDependency) -> Self {
+        JsonDependency {
+            required: &dep.required,
+            target: dep.target.as_deref(),
+            optional: dep.optional,
+            features: dep.features.iter().map(String::as_str).collect(),
+            registry: dep.registry.as_ref().map(|registry| registry.as_ref()),
+            latest_that_matches: dep.latest_that_matches.as_ref(),
+            latest: dep.latest.as_ref(),
+            outdated: dep.is_outdated(),
+            insecure: dep.is_insecure(),
+            pinned: dep.pinned,
+            advisories: dep
+                .vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().as_str())
+                .collect(),
+            ignored_advisories: dep
+                .ignored_vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().as_str())
+                .collect(),
+            platforms: dep.platforms.iter().map(JsonDependency::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonGitDependency<'a> {
+    url: &'a str,
+    rev: Option<&'a str>,
+    branch: Option<&'a str>,
+    tag: Option<&'a str>,
+    latest: Option<&'a Version>,
+}
+
+impl<'a> From<&'a AnalyzedGitDependency> for JsonGitDependency<'a> {
+    fn from(dep: &'a AnalyzedGitDependency) -> Self {
+        let (rev, branch, tag) = match dep.git.pin {
+            GitPin::Rev(ref rev) => (Some(rev.as_str()), None, None),
+            GitPin::Branch(ref branch) => (None, Some(branch.as_str()), None),
+            GitPin::Tag(ref tag) => (None, None, Some(tag.as_str())),
+            GitPin::DefaultBranch => (None, None, None),
+        };
+
+        JsonGitDependency {
+            url: &dep.git.url,
+            rev,
+            branch,
+            tag,
+            latest: dep.latest.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonCrate<'a> {
+    name: &'a str,
+    dependencies: IndexMap<&'a str, JsonDependency<'a>>,
+    dev_dependencies: IndexMap<&'a str, JsonDependency<'a>>,
+    build_dependencies: IndexMap<&'a str, JsonDependency<'a>>,
+    git_dependencies: IndexMap<&'a str, JsonGitDependency<'a>>,
+    ignored: Vec<&'a str>,
+}
+
+fn convert_deps(
+    deps: &IndexMap<CrateName, AnalyzedDependency>,
+) -> IndexMap<&str, JsonDependency<'_>> {
+    deps.iter()
+        .map(|(name, dep)| (name.as_ref(), JsonDependency::from(dep)))
+        .collect()
+}
+
+impl<'a> JsonCrate<'a> {
+    fn new(name: &'a CrateName, deps: &'a AnalyzedDependencies) -> Self {
+        JsonCrate {
+            name: name.as_ref(),
+            dependencies: convert_deps(&deps.main),
+            dev_dependencies: convert_deps(&deps.dev),
+            build_dependencies: convert_deps(&deps.build),
+            git_dependencies: deps
+                .git
+                .iter()
+                .map(|(name, dep)| (name.as_ref(), JsonGitDependency::from(dep)))
+                .collect(),
+            ignored: deps.ignored.iter().map(|name| name.as_ref()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLockedPackage<'a> {
+    name: &'a str,
+    version: &'a Version,
+    yanked: bool,
+    insecure: bool,
+    advisories: Vec<&'a str>,
+    ignored_advisories: Vec<&'a str>,
+}
+
+impl<'a> From<&'a AnalyzedLockedPackage> for JsonLockedPackage<'a> {
+    fn from(pkg: &'a AnalyzedLockedPackage) -> Self {
+        JsonLockedPackage {
+            name: pkg.name.as_ref(),
+            version: &pkg.version,
+            yanked: pkg.yanked,
+            insecure: pkg.is_insecure(),
+            advisories: pkg
+                .vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().as_str())
+                .collect(),
+            ignored_advisories: pkg
+                .ignored_vulnerabilities
+                .iter()
+                .map(|advisory| advisory.id().as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonTransitiveDependency<'a> {
+    name: &'a str,
+    path: Vec<&'a str>,
+    #[serde(flatten)]
+    dependency: JsonDependency<'a>,
+}
+
+impl<'a> From<&'a TransitiveDependency> for JsonTransitiveDependency<'a> {
+    fn from(dep: &'a TransitiveDependency) -> Self {
+        JsonTransitiveDependency {
+            name: dep.name.as_ref(),
+            path: dep.path.iter().map(|name| name.as_ref()).collect(),
+            dependency: JsonDependency::from(&dep.analyzed),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutcome<'a> {
+    insecure: bool,
+    outdated: usize,
+    total: usize,
+    crates: Vec<JsonCrate<'a>>,
+    lockfile: Option<Vec<JsonLockedPackage<'a>>>,
+    lockfile_ignored: Vec<&'a str>,
+    tree: Option<Vec<JsonTransitiveDependency<'a>>>,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    error: &'a str,
+    kind: FailureKind,
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let body = serde_json::to_vec(value).expect("failed to serialize status");
+
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// The JSON representation of an outcome, as served by `status.json`
+pub fn outcome(outcome: &AnalyzeDependenciesOutcome) -> impl Serialize + '_ {
+    let (outdated, total) = outcome.outdated_ratio();
+    JsonOutcome {
+        insecure: outcome.any_insecure(),
+        outdated,
+        total,
+        crates: outcome
+            .crates
+            .iter()
+            .map(|(name, deps)| JsonCrate::new(name, deps))
+            .collect(),
+        lockfile: outcome.lockfile.as_ref().map(|lockfile| {
+            lockfile
+                .packages
+                .iter()
+                .map(JsonLockedPackage::from)
+                .collect()
+        }),
+        lockfile_ignored: outcome
+            .lockfile
+            .iter()
+            .flat_map(|lockfile| &lockfile.ignored)
+            .map(|name| name.as_ref())
+            .collect(),
+        tree: outcome.tree.as_ref().map(|tree| {
+            tree.dependencies
+                .iter()
+                .map(JsonTransitiveDependency::from)
+                .collect()
+        }),
+    }
+}
+
+pub fn response(
+    analysis_outcome: Result<&AnalyzeDependenciesOutcome, FailureKind>,
+) -> Response<Body> {
+    match analysis_outcome {
+        Ok(analysis_outcome) => json_response(StatusCode::OK, &outcome(analysis_outcome)),
+        Err(kind) => {
+            let status = match kind {
+                FailureKind::NotFound => StatusCode::NOT_FOUND,
+                FailureKind::ParseError => StatusCode::UNPROCESSABLE_ENTITY,
+                FailureKind::RateLimited => StatusCode::SERVICE_UNAVAILABLE,
+                FailureKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+                FailureKind::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+
+            json_response(
+                status,
+                &JsonError {
+                    error: "failed to analyze dependencies",
+                    kind,
+                },
+            )
+        }
+    }
+}
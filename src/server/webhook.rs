@@ -0,0 +1,267 @@
+use anyhow::{anyhow, bail, Error};
+use derive_more::Display;
+use hmac::{Hmac, Mac, NewMac};
+use hyper::HeaderMap;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::config::AccessToken;
+use crate::models::repo::{CustomSite, CustomSiteKind, GitRef, RepoPath, RepoSite};
+
+/// Error of a push to a GitLab subgroup, whose repositories can't be analyzed since their
+/// paths have more than two segments
+#[derive(Debug, Display)]
+#[display(fmt = "repository {:?} is in a nested namespace", _0)]
+pub struct NestedNamespace(pub String);
+
+impl std::error::Error for NestedNamespace {}
+
+/// Software that sends the webhooks of a site, which determines their format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Provider {
+    Github,
+    Gitlab,
+    Gitea,
+}
+
+impl Provider {
+    pub fn of_site(site: &RepoSite) -> Option<Provider> {
+        match site {
+            RepoSite::Github => Some(Provider::Github),
+            RepoSite::Gitlab => Some(Provider::Gitlab),
+            RepoSite::Bitbucket => None,
+            RepoSite::Custom(CustomSite { kind, .. }) => Some(match kind {
+                CustomSiteKind::Gitlab => Provider::Gitlab,
+                CustomSiteKind::Gitea => Provider::Gitea,
+                CustomSiteKind::GithubEnterprise => Provider::Github,
+            }),
+        }
+    }
+
+    /// Checks that the request was sent by the site, using the shared secret
+    pub fn verify(self, headers: &HeaderMap, body: &[u8], secret: &AccessToken) -> bool {
+        let verified = match self {
+            Provider::Github => header(headers, "x-hub-signature-256")
+                .and_then(|sig| sig.strip_prefix("sha256="))
+                .map(|sig| verify_hmac(secret, body, sig)),
+            Provider::Gitea => {
+                header(headers, "x-gitea-signature").map(|sig| verify_hmac(secret, body, sig))
+            }
+            // GitLab sends the secret itself instead of signing the payload
+            Provider::Gitlab => header(headers, "x-gitlab-token")
+                .map(|token| constant_time_eq(token.as_bytes(), secret.secret().as_bytes())),
+        };
+
+        verified == Some(true)
+    }
+
+    /// Returns whether the request announces a push of a branch or tag
+    pub fn is_push(self, headers: &HeaderMap) -> bool {
+        match self {
+            Provider::Github => header(headers, "x-github-event") == Some("push"),
+            Provider::Gitea => header(headers, "x-gitea-event") == Some("push"),
+            Provider::Gitlab => matches!(
+                header(headers, "x-gitlab-event"),
+                Some("Push Hook") | Some("Tag Push Hook")
+            ),
+        }
+    }
+
+    pub fn parse_push(self, site: RepoSite, body: &[u8]) -> Result<PushEvent, Error> {
+        let (git_ref, full_name, default_branch) = match self {
+            Provider::Github | Provider::Gitea => {
+                let payload: GithubPush = serde_json::from_slice(body)?;
+                (
+                    payload.git_ref,
+                    payload.repository.full_name,
+                    payload.repository.default_branch,
+                )
+            }
+            Provider::Gitlab => {
+                let payload: GitlabPush = serde_json::from_slice(body)?;
+                (
+                    payload.git_ref,
+                    payload.project.path_with_namespace,
+                    payload.project.default_branch,
+                )
+            }
+        };
+
+        let (qual, name) = full_name
+            .rsplit_once('/')
+            .ok_or_else(|| anyhow!("invalid repository name {:?}", full_name))?;
+        if qual.contains('/') {
+            return Err(NestedNamespace(full_name).into());
+        }
+        let repo_path = RepoPath::from_parts(site.as_ref(), qual, name)?;
+
+        let (pushed, is_default_branch) = if let Some(branch) = git_ref.strip_prefix("refs/heads/")
+        {
+            (branch, default_branch.as_deref() == Some(branch))
+        } else if let Some(tag) = git_ref.strip_prefix("refs/tags/") {
+            (tag, false)
+        } else {
+            bail!("unsupported ref {:?}", git_ref);
+        };
+
+        Ok(PushEvent {
+            repo_path,
+            git_ref: pushed.parse()?,
+            is_default_branch,
+        })
+    }
+}
+
+/// A push of a branch or tag
+#[derive(Debug)]
+pub struct PushEvent {
+    /// Spelled like the site reports it, which cached outcomes are matched with regardless
+    /// of case
+    pub repo_path: RepoPath,
+    pub git_ref: GitRef,
+    pub is_default_branch: bool,
+}
+
+impl PushEvent {
+    /// The repository paths whose analysis is affected by the push
+    pub fn affected_paths(&self) -> Vec<RepoPath> {
+        let mut paths = vec![self
+            .repo_path
+            .clone()
+            .with_git_ref(Some(self.git_ref.clone()))];
+        if self.is_default_branch {
+            paths.push(self.repo_path.clone().with_git_ref(None));
+        }
+        paths
+    }
+}
+
+#[derive(Deserialize)]
+struct GithubPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    repository: GithubRepository,
+}
+
+#[derive(Deserialize)]
+struct GithubRepository {
+    full_name: String,
+    default_branch: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitlabPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    project: GitlabProject,
+}
+
+#[derive(Deserialize)]
+struct GitlabProject {
+    path_with_namespace: String,
+    default_branch: Option<String>,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn verify_hmac(secret: &AccessToken, body: &[u8], signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::HeaderValue;
+
+    use super::*;
+
+    fn secret() -> AccessToken {
+        serde_json::from_str("\"It's a Secret to Everybody\"").unwrap()
+    }
+
+    #[test]
+    fn verifies_github_signatures() {
+        // example from GitHub's documentation on validating webhook deliveries
+        let body = b"Hello, World!";
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hub-signature-256", HeaderValue::from_static(signature));
+        assert!(Provider::Github.verify(&headers, body, &secret()));
+        assert!(!Provider::Github.verify(&headers, b"Hello, World?", &secret()));
+        assert!(!Provider::Gitea.verify(&headers, body, &secret()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-gitea-signature",
+            HeaderValue::from_static(&signature[7..]),
+        );
+        assert!(Provider::Gitea.verify(&headers, body, &secret()));
+    }
+
+    #[test]
+    fn verifies_gitlab_tokens() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-gitlab-token",
+            HeaderValue::from_static("It's a Secret to Everybody"),
+        );
+        assert!(Provider::Gitlab.verify(&headers, b"{}", &secret()));
+
+        headers.insert("x-gitlab-token", HeaderValue::from_static("guess"));
+        assert!(!Provider::Gitlab.verify(&headers, b"{}", &secret()));
+    }
+
+    #[test]
+    fn parses_pushes_to_the_default_branch() {
+        let body = br#"{
+            "ref": "refs/heads/main",
+            "repository": { "full_name": "deps-rs/deps.rs", "default_branch": "main" }
+        }"#;
+
+        let push = Provider::Github.parse_push(RepoSite::Github, body).unwrap();
+        let paths = push.affected_paths();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].git_ref.as_ref().unwrap().as_ref(), "main");
+        assert_eq!(paths[1].git_ref, None);
+        assert_eq!(paths[1].name.as_ref(), "deps.rs");
+    }
+
+    #[test]
+    fn parses_gitlab_tag_pushes() {
+        let body = br#"{
+            "ref": "refs/tags/v1.0.0",
+            "project": { "path_with_namespace": "group/project", "default_branch": "main" },
+            "repository": { "name": "project" }
+        }"#;
+
+        let push = Provider::Gitlab.parse_push(RepoSite::Gitlab, body).unwrap();
+        assert!(!push.is_default_branch);
+        assert_eq!(push.git_ref.as_ref(), "v1.0.0");
+        assert_eq!(push.repo_path.qual.as_ref(), "group");
+
+        let body = br#"{
+            "ref": "refs/tags/v1.0.0",
+            "project": { "path_with_namespace": "group/subgroup/project", "default_branch": "main" },
+            "repository": { "name": "project" }
+        }"#;
+
+        let err = Provider::Gitlab
+            .parse_push(RepoSite::Gitlab, body)
+            .unwrap_err();
+        assert!(err.is::<NestedNamespace>());
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use rustsec::{database::Database, repository::git::Repository};
+use slog::{error, info, Logger};
+use tokio::task::spawn_blocking;
+use tokio::time::{self, Interval};
+
+/// Shared handle to the most recently loaded advisory database.
+#[derive(Clone, Default)]
+pub struct AdvisoryDb {
+    current: Arc<RwLock<Option<Arc<Database>>>>,
+}
+
+impl AdvisoryDb {
+    /// Returns the current database, or `None` if it has not been loaded yet.
+    pub fn get(&self) -> Option<Arc<Database>> {
+        self.current.read().unwrap().clone()
+    }
+
+    fn replace(&self, db: Database) {
+        *self.current.write().unwrap() = Some(Arc::new(db));
+    }
+}
+
+impl fmt::Debug for AdvisoryDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AdvisoryDb")
+    }
+}
+
+#[derive(Debug)]
+pub struct ManagedAdvisoryDb {
+    db: AdvisoryDb,
+    update_interval: Interval,
+    logger: Logger,
+}
+
+impl ManagedAdvisoryDb {
+    pub fn new(update_interval: Duration, logger: Logger) -> Self {
+        let update_interval = time::interval(update_interval);
+        Self {
+            db: AdvisoryDb::default(),
+            update_interval,
+            logger,
+        }
+    }
+
+    pub fn db(&self) -> AdvisoryDb {
+        self.db.clone()
+    }
+
+    pub async fn initial_clone(&mut self) -> Result<()> {
+        let logger = self.logger.clone();
+
+        let db = spawn_blocking(move || {
+            // the repository path is configurable through the `CARGO_HOME` env variable
+            let path = Repository::default_path();
+            let repo = if path.exists() {
+                Repository::open(path)?
+            } else {
+                info!(logger, "Cloning advisory-db");
+                Repository::fetch_default_repo()?
+            };
+            Ok::<_, Error>(Database::load_from_repo(&repo)?)
+        })
+        .await??;
+
+        self.db.replace(db);
+        Ok(())
+    }
+
+    pub async fn refresh_at_interval(&mut self) {
+        loop {
+            if let Err(e) = self.refresh().await {
+                error!(
+                    self.logger,
+                    "failed refreshing the advisory-db, the operation will be retried: {}", e
+                );
+            }
+            self.update_interval.tick().await;
+        }
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let db = spawn_blocking(|| {
+            let repo = Repository::fetch_default_repo()?;
+            Ok::<_, Error>(Database::load_from_repo(&repo)?)
+        })
+        .await??;
+
+        self.db.replace(db);
+        Ok(())
+    }
+}
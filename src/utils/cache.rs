@@ -1,16 +1,50 @@
-use std::{fmt, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    error, fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use derive_more::{Display, Error, From};
+use anyhow::Error;
+use derive_more::Display;
+use futures::future::{FutureExt as _, Shared};
 use hyper::service::Service;
 use lru_time_cache::LruCache;
-use slog::{debug, Logger};
+use slog::{debug, warn, Logger};
 use tokio::sync::Mutex;
+use tokio::time::Instant;
 
-#[derive(Debug, Clone, Display, From, Error)]
-pub struct CacheError<E> {
-    inner: E,
+use crate::BoxFuture;
+
+/// Error of a failed upstream query, shared by every caller that was waiting for it
+#[derive(Debug, Clone, Display)]
+#[display(fmt = "{}", inner)]
+pub struct CacheError {
+    inner: Arc<Error>,
+}
+
+impl CacheError {
+    /// The error of the upstream query
+    pub fn inner(&self) -> &Error {
+        &self.inner
+    }
 }
 
+impl error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+type InFlight<T, E = CacheError> = Shared<BoxFuture<Result<T, E>>>;
+
+/// An in-flight computation, along with a tag that is unique to it
+type TaggedInFlight<T, E> = (u64, InFlight<T, E>);
+
 #[derive(Clone)]
 pub struct Cache<S, Req>
 where
@@ -18,6 +52,7 @@ where
 {
     inner: S,
     cache: Arc<Mutex<LruCache<Req, S::Response>>>,
+    in_flight: Arc<Mutex<BTreeMap<Req, InFlight<S::Response>>>>,
     logger: Logger,
 }
 
@@ -34,9 +69,11 @@ where
 
 impl<S, Req> Cache<S, Req>
 where
-    S: Service<Req> + fmt::Debug + Clone,
-    S::Response: Clone,
-    Req: Clone + Eq + Ord + fmt::Debug,
+    S: Service<Req> + fmt::Debug + Clone + Send + 'static,
+    S::Response: Clone + Send + Sync + 'static,
+    S::Error: Into<Error>,
+    S::Future: Send + 'static,
+    Req: Clone + Eq + Ord + fmt::Debug + Send + Sync + 'static,
 {
     pub fn new(service: S, ttl: Duration, capacity: usize, logger: Logger) -> Cache<S, Req> {
         let cache = LruCache::with_expiry_duration_and_capacity(ttl, capacity);
@@ -44,12 +81,15 @@ where
         Cache {
             inner: service,
             cache: Arc::new(Mutex::new(cache)),
+            in_flight: Arc::new(Mutex::new(BTreeMap::new())),
             logger,
         }
     }
 
-    pub async fn cached_query(&self, req: Req) -> Result<S::Response, S::Error> {
-        {
+    /// Returns the cached response, or queries the inner service. Concurrent misses for the
+    /// same request share a single query.
+    pub async fn cached_query(&self, req: Req) -> Result<S::Response, Error> {
+        let query = {
             let mut cache = self.cache.lock().await;
 
             if let Some(cached_response) = cache.get(&req) {
@@ -60,22 +100,421 @@ where
                 );
                 return Ok(cached_response.clone());
             }
-        }
 
-        debug!(
-            self.logger, "cache miss";
-            "svc" => format!("{:?}", self.inner),
-            "req" => format!("{:?}", &req)
-        );
+            // the cache lock is held until the query is registered, so that a query finishing
+            // in the meantime can't be missed
+            let mut in_flight = self.in_flight.lock().await;
+
+            match in_flight.get(&req) {
+                Some(query) => {
+                    debug!(
+                        self.logger, "cache miss, joining in-flight query";
+                        "svc" => format!("{:?}", self.inner),
+                        "req" => format!("{:?}", &req)
+                    );
+                    query.clone()
+                }
+                None => {
+                    debug!(
+                        self.logger, "cache miss";
+                        "svc" => format!("{:?}", self.inner),
+                        "req" => format!("{:?}", &req)
+                    );
+                    let query = self.query(req.clone()).shared();
+                    in_flight.insert(req, query.clone());
+                    query
+                }
+            }
+        };
+
+        Ok(query.await?)
+    }
 
+    fn query(&self, req: Req) -> BoxFuture<Result<S::Response, CacheError>> {
         let mut service = self.inner.clone();
-        let fresh = service.call(req.clone()).await?;
+        let cache = self.cache.clone();
+        let in_flight = self.in_flight.clone();
 
-        {
+        async move {
+            let result = service.call(req.clone()).await.map_err(|err| CacheError {
+                inner: Arc::new(err.into()),
+            });
+
+            if let Ok(ref fresh) = result {
+                cache.lock().await.insert(req.clone(), fresh.clone());
+            }
+            in_flight.lock().await.remove(&req);
+
+            result
+        }
+        .boxed()
+    }
+}
+
+struct Entry<T> {
+    value: T,
+    stored_at: Instant,
+}
+
+impl<T> Entry<T> {
+    fn new(value: T) -> Entry<T> {
+        Entry {
+            value,
+            stored_at: Instant::now(),
+        }
+    }
+}
+
+/// Cache that keeps serving a value after `soft_ttl` while it is recomputed in the background.
+/// Values are only dropped once they reach the hard TTL or get evicted. Errors are cached too,
+/// for `failure_ttl`, unless an older value can be served instead.
+#[derive(Clone)]
+pub struct RevalidatingCache<K, T, E> {
+    cache: Arc<Mutex<LruCache<K, Entry<T>>>>,
+    failures: Arc<Mutex<LruCache<K, Entry<E>>>>,
+    /// Running computations, tagged so that invalidated ones don't store their results
+    in_flight: Arc<Mutex<BTreeMap<K, TaggedInFlight<T, E>>>>,
+    next_tag: Arc<AtomicU64>,
+    soft_ttl: Duration,
+    failure_ttl: Duration,
+    logger: Logger,
+}
+
+impl<K, T, E> fmt::Debug for RevalidatingCache<K, T, E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RevalidatingCache")
+            .field("soft_ttl", &self.soft_ttl)
+            .finish()
+    }
+}
+
+impl<K, T, E> RevalidatingCache<K, T, E>
+where
+    K: Clone + Ord + fmt::Debug + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+    E: Clone + fmt::Display + Send + Sync + 'static,
+{
+    pub fn new(
+        soft_ttl: Duration,
+        hard_ttl: Duration,
+        failure_ttl: Duration,
+        capacity: usize,
+        logger: Logger,
+    ) -> RevalidatingCache<K, T, E> {
+        let cache = LruCache::with_expiry_duration_and_capacity(hard_ttl, capacity);
+        let failures = LruCache::with_capacity(capacity);
+
+        RevalidatingCache {
+            cache: Arc::new(Mutex::new(cache)),
+            failures: Arc::new(Mutex::new(failures)),
+            in_flight: Arc::new(Mutex::new(BTreeMap::new())),
+            next_tag: Arc::new(AtomicU64::new(0)),
+            soft_ttl,
+            failure_ttl,
+            logger,
+        }
+    }
+
+    /// Returns the cached value for `key`, starting a background refresh if it is stale.
+    /// On a miss, waits for `compute`, sharing it with concurrent callers.
+    pub async fn get_or_compute<F, Fut>(&self, key: K, compute: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let query = {
             let mut cache = self.cache.lock().await;
-            cache.insert(req, fresh.clone());
+
+            let cached = cache.get(&key).map(|entry| {
+                (
+                    entry.value.clone(),
+                    entry.stored_at.elapsed() < self.soft_ttl,
+                )
+            });
+
+            if let Some((value, true)) = cached {
+                debug!(self.logger, "cache hit"; "key" => format!("{:?}", &key));
+                return Ok(value);
+            }
+
+            if cached.is_none() {
+                let mut failures = self.failures.lock().await;
+                let failure = failures
+                    .get(&key)
+                    .filter(|failure| failure.stored_at.elapsed() < self.failure_ttl);
+                if let Some(failure) = failure {
+                    debug!(self.logger, "cached failure"; "key" => format!("{:?}", &key));
+                    return Err(failure.value.clone());
+                }
+            }
+
+            let mut in_flight = self.in_flight.lock().await;
+
+            let query = match in_flight.get(&key) {
+                Some((_, query)) => query.clone(),
+                None => {
+                    let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+                    let query = self.query(key.clone(), tag, compute()).shared();
+                    in_flight.insert(key.clone(), (tag, query.clone()));
+
+                    // nobody waits for a background refresh, so it has to be driven separately
+                    if cached.is_some() {
+                        let logger = self.logger.clone();
+                        let key = key.clone();
+                        let refresh = query.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = refresh.await {
+                                warn!(
+                                    logger, "background refresh failed";
+                                    "key" => format!("{:?}", key),
+                                    "error" => err.to_string()
+                                );
+                            }
+                        });
+                    }
+                    query
+                }
+            };
+
+            if let Some((stale, _)) = cached {
+                debug!(self.logger, "stale cache hit"; "key" => format!("{:?}", &key));
+                return Ok(stale);
+            }
+
+            debug!(self.logger, "cache miss"; "key" => format!("{:?}", &key));
+            query
+        };
+
+        query.await
+    }
+
+    /// Drops everything known about `key`, so that the next lookup computes it anew. Returns
+    /// whether the key had been cached or was being computed.
+    pub async fn invalidate(&self, key: &K) -> bool {
+        let mut cache = self.cache.lock().await;
+        let mut failures = self.failures.lock().await;
+        let mut in_flight = self.in_flight.lock().await;
+
+        let had_value = cache.remove(key).is_some();
+        let had_failure = failures.remove(key).is_some();
+        let was_computing = in_flight.remove(key).is_some();
+
+        had_value || had_failure || was_computing
+    }
+
+    fn query(
+        &self,
+        key: K,
+        tag: u64,
+        compute: impl Future<Output = Result<T, E>> + Send + 'static,
+    ) -> BoxFuture<Result<T, E>> {
+        let cache = self.cache.clone();
+        let failures = self.failures.clone();
+        let in_flight = self.in_flight.clone();
+
+        async move {
+            let result = compute.await;
+
+            let mut cache = cache.lock().await;
+            let mut failures = failures.lock().await;
+            let mut in_flight = in_flight.lock().await;
+
+            // the result of an invalidated computation is outdated, and must not replace newer ones
+            if in_flight.get(&key).map(|&(current, _)| current) == Some(tag) {
+                in_flight.remove(&key);
+
+                match result {
+                    Ok(ref fresh) => {
+                        cache.insert(key.clone(), Entry::new(fresh.clone()));
+                        failures.remove(&key);
+                    }
+                    Err(ref err) => {
+                        failures.insert(key, Entry::new(err.clone()));
+                    }
+                }
+            }
+
+            result
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::task::{Context, Poll};
+
+    use futures::future::join_all;
+    use slog::o;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::engine::{AnalysisFailure, FailureKind};
+    use crate::interactors::crates::CrateNotFound;
+
+    #[derive(Clone, Debug, Default)]
+    struct CountingService {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Service<u32> for CountingService {
+        type Response = u32;
+        type Error = Error;
+        type Future = BoxFuture<Result<u32, Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
         }
 
-        Ok(fresh)
+        fn call(&mut self, req: u32) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                if req == 0 {
+                    Err(CrateNotFound("zero".parse().unwrap()).into())
+                } else {
+                    Ok(req * 2)
+                }
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_share_one_query() {
+        let service = CountingService::default();
+        let calls = service.calls.clone();
+        let cache = Cache::new(
+            service,
+            Duration::from_secs(60),
+            10,
+            Logger::root(slog::Discard, o!()),
+        );
+
+        let results = join_all((0..10).map(|_| cache.cached_query(21))).await;
+        assert!(results.iter().all(|res| matches!(res, Ok(42))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let errors = join_all((0..10).map(|_| cache.cached_query(0))).await;
+        assert!(errors.iter().all(|res| res.is_err()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // failures are not cached
+        assert!(cache.cached_query(0).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        assert_eq!(cache.cached_query(21).await.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn failures_keep_their_kind() {
+        let cache = Cache::new(
+            CountingService::default(),
+            Duration::from_secs(60),
+            10,
+            Logger::root(slog::Discard, o!()),
+        );
+
+        let err = cache.cached_query(0).await.unwrap_err();
+        assert_eq!(AnalysisFailure::from(err).kind, FailureKind::NotFound);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serves_stale_values_while_revalidating() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let compute = || {
+            let calls = calls.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok::<_, String>(calls.fetch_add(1, Ordering::SeqCst))
+            }
+        };
+        let cache = RevalidatingCache::new(
+            Duration::from_millis(50),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            10,
+            Logger::root(slog::Discard, o!()),
+        );
+
+        assert_eq!(cache.get_or_compute("key", compute).await.unwrap(), 0);
+        assert_eq!(cache.get_or_compute("key", compute).await.unwrap(), 0);
+
+        tokio::time::advance(Duration::from_millis(60)).await;
+
+        // the stale value is returned right away, and only one refresh is started
+        let stale = join_all((0..5).map(|_| cache.get_or_compute("key", compute))).await;
+        assert!(stale.iter().all(|res| matches!(res, Ok(0))));
+
+        // lets the paused clock run until the refresh has completed
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(cache.get_or_compute("key", compute).await.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caches_failures_briefly() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let compute = || {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<u32, _>("not found".to_string())
+            }
+        };
+        let cache = RevalidatingCache::new(
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            Duration::from_millis(50),
+            10,
+            Logger::root(slog::Discard, o!()),
+        );
+
+        assert!(cache.get_or_compute("key", compute).await.is_err());
+        assert!(cache.get_or_compute("key", compute).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_millis(60)).await;
+        assert!(cache.get_or_compute("key", compute).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn invalidated_computations_are_not_stored() {
+        let cache = RevalidatingCache::new(
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            10,
+            Logger::root(slog::Discard, o!()),
+        );
+
+        // the slow computation is polled first, and only finishes once the key was invalidated
+        let (finish, finished) = oneshot::channel::<()>();
+        let slow = cache.get_or_compute("key", move || async move {
+            finished.await.unwrap();
+            Ok::<_, String>("before push")
+        });
+        let invalidate = async {
+            assert!(!cache.invalidate(&"other").await);
+            assert!(cache.invalidate(&"key").await);
+            let fresh = cache
+                .get_or_compute("key", || async { Ok("after push") })
+                .await;
+            finish.send(()).unwrap();
+            fresh
+        };
+
+        let (slow, fresh) = futures::join!(slow, invalidate);
+        assert_eq!(slow.unwrap(), "before push");
+        assert_eq!(fresh.unwrap(), "after push");
+
+        let cached = cache
+            .get_or_compute("key", || async { Ok("recomputed") })
+            .await;
+        assert_eq!(cached.unwrap(), "after push");
     }
 }
@@ -0,0 +1,321 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection};
+use tokio::task::spawn_blocking;
+
+use crate::models::SubjectPath;
+
+/// Number of analyses kept per repository ref or crate release, older ones are dropped
+pub const MAX_ENTRIES_PER_SUBJECT: usize = 100;
+
+// `repo` and `git_ref` hold the crate and its version for crate analyses
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS analyses (
+        id INTEGER PRIMARY KEY,
+        repo TEXT NOT NULL,
+        git_ref TEXT NOT NULL,
+        analyzed_at INTEGER NOT NULL,
+        outdated INTEGER NOT NULL,
+        insecure INTEGER NOT NULL,
+        total INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS analyses_by_repo ON analyses (repo, git_ref, analyzed_at);
+    CREATE TABLE IF NOT EXISTS analyzed_dependencies (
+        analysis_id INTEGER NOT NULL REFERENCES analyses (id),
+        crate_name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        required TEXT NOT NULL,
+        latest TEXT,
+        outdated INTEGER NOT NULL,
+        insecure INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS analyzed_dependencies_by_analysis
+        ON analyzed_dependencies (analysis_id);
+";
+
+/// Counts and dependency versions of a single analysis, as stored in the history
+#[derive(Debug)]
+pub struct AnalysisSummary {
+    pub outdated: usize,
+    pub insecure: usize,
+    pub total: usize,
+    pub dependencies: Vec<DependencySummary>,
+}
+
+#[derive(Debug)]
+pub struct DependencySummary {
+    /// Workspace member declaring the dependency
+    pub crate_name: String,
+    /// `normal`, `dev` or `build`
+    pub kind: &'static str,
+    pub name: String,
+    pub required: String,
+    pub latest: Option<String>,
+    pub outdated: bool,
+    pub insecure: bool,
+}
+
+#[derive(Debug)]
+pub struct HistoryEntry {
+    /// UTC time of the analysis, formatted as `YYYY-MM-DD HH:MM`
+    pub analyzed_at: String,
+    pub outdated: usize,
+    pub insecure: usize,
+    pub total: usize,
+}
+
+/// Analysis history of repositories and crates, stored in SQLite
+#[derive(Clone, Debug)]
+pub struct History {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<History> {
+        History::init(Connection::open(path)?)
+    }
+
+    /// Keeps the history in memory only, losing it on restart
+    pub fn in_memory() -> Result<History> {
+        History::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<History> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(History {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Records an analysis, dropping the oldest ones of the subject beyond
+    /// `MAX_ENTRIES_PER_SUBJECT`
+    pub async fn record(&self, subject_path: &SubjectPath, summary: AnalysisSummary) -> Result<()> {
+        let (repo, git_ref) = history_key(subject_path);
+        let analyzed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO analyses (repo, git_ref, analyzed_at, outdated, insecure, total)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    repo,
+                    git_ref,
+                    analyzed_at,
+                    summary.outdated as i64,
+                    summary.insecure as i64,
+                    summary.total as i64
+                ],
+            )?;
+            let analysis_id = tx.last_insert_rowid();
+
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO analyzed_dependencies
+                     (analysis_id, crate_name, kind, name, required, latest, outdated, insecure)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )?;
+                for dep in summary.dependencies {
+                    insert.execute(params![
+                        analysis_id,
+                        dep.crate_name,
+                        dep.kind,
+                        dep.name,
+                        dep.required,
+                        dep.latest,
+                        dep.outdated,
+                        dep.insecure
+                    ])?;
+                }
+            }
+
+            tx.execute(
+                "DELETE FROM analyzed_dependencies WHERE analysis_id IN (
+                     SELECT id FROM analyses WHERE repo = ?1 AND git_ref = ?2
+                     ORDER BY analyzed_at DESC, id DESC LIMIT -1 OFFSET ?3
+                 )",
+                params![repo, git_ref, MAX_ENTRIES_PER_SUBJECT as i64],
+            )?;
+            tx.execute(
+                "DELETE FROM analyses WHERE id IN (
+                     SELECT id FROM analyses WHERE repo = ?1 AND git_ref = ?2
+                     ORDER BY analyzed_at DESC, id DESC LIMIT -1 OFFSET ?3
+                 )",
+                params![repo, git_ref, MAX_ENTRIES_PER_SUBJECT as i64],
+            )?;
+
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns the most recent analyses of a repository or crate, newest first
+    pub async fn load(
+        &self,
+        subject_path: &SubjectPath,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>> {
+        let (repo, git_ref) = history_key(subject_path);
+
+        self.with_conn(move |conn| {
+            let mut query = conn.prepare(
+                "SELECT strftime('%Y-%m-%d %H:%M', analyzed_at, 'unixepoch'),
+                        outdated, insecure, total
+                 FROM analyses
+                 WHERE repo = ?1 AND git_ref = ?2
+                 ORDER BY analyzed_at DESC, id DESC
+                 LIMIT ?3",
+            )?;
+
+            let entries = query
+                .query_map(params![repo, git_ref, limit as i64], |row| {
+                    Ok(HistoryEntry {
+                        analyzed_at: row.get(0)?,
+                        outdated: row.get::<_, i64>(1)? as usize,
+                        insecure: row.get::<_, i64>(2)? as usize,
+                        total: row.get::<_, i64>(3)? as usize,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(entries)
+        })
+        .await
+    }
+
+    async fn with_conn<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let conn = self.conn.clone();
+
+        spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow!("history database lock is poisoned"))?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+fn history_key(subject_path: &SubjectPath) -> (String, String) {
+    match subject_path {
+        SubjectPath::Repo(repo_path) => {
+            // same spelling as the analysis subjects, which ignore the case of owner and name
+            let repo_path = repo_path.to_lowercase();
+            let repo = format!(
+                "{}/{}/{}",
+                repo_path.site.as_ref(),
+                repo_path.qual.as_ref(),
+                repo_path.name.as_ref()
+            );
+            let git_ref = repo_path
+                .git_ref
+                .as_ref()
+                .map(|git_ref| git_ref.as_ref().to_string())
+                .unwrap_or_default();
+
+            (repo, git_ref)
+        }
+        SubjectPath::Crate(crate_path) => {
+            let registry = crate_path
+                .registry
+                .as_ref()
+                .map_or("crates.io", |registry| registry.as_ref());
+            let repo = format!("crate/{}/{}", registry, crate_path.name.as_ref());
+
+            (repo, crate_path.version.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{crates::CratePath, repo::RepoPath};
+
+    fn summary(outdated: usize, insecure: usize) -> AnalysisSummary {
+        AnalysisSummary {
+            outdated,
+            insecure,
+            total: 3,
+            dependencies: vec![DependencySummary {
+                crate_name: "deps-rs".to_string(),
+                kind: "normal",
+                name: "hyper".to_string(),
+                required: "^0.13".to_string(),
+                latest: Some("0.14.2".to_string()),
+                outdated: true,
+                insecure: false,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_recorded_analyses_per_ref() {
+        let history = History::in_memory().unwrap();
+        let repo_path = RepoPath::from_parts("github", "deps-rs", "deps.rs").unwrap();
+        let branch_path = SubjectPath::Repo(
+            repo_path
+                .clone()
+                .with_git_ref(Some("develop".parse().unwrap())),
+        );
+        let repo_path = SubjectPath::Repo(repo_path);
+
+        history.record(&repo_path, summary(2, 1)).await.unwrap();
+        history.record(&repo_path, summary(1, 0)).await.unwrap();
+        history.record(&branch_path, summary(3, 0)).await.unwrap();
+
+        let entries = history.load(&repo_path, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].outdated, entries[0].insecure), (1, 0));
+        assert_eq!((entries[1].outdated, entries[1].insecure), (2, 1));
+        assert_eq!(entries[0].analyzed_at.len(), "2021-01-01 00:00".len());
+
+        let entries = history.load(&branch_path, 10).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].outdated, 3);
+
+        let mixed_case_path =
+            SubjectPath::Repo(RepoPath::from_parts("github", "Deps-RS", "Deps.rs").unwrap());
+        let entries = history.load(&mixed_case_path, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn keeps_recent_analyses_of_crates() {
+        let history = History::in_memory().unwrap();
+        let crate_path = SubjectPath::Crate(CratePath::from_parts("hyper", "0.14.2").unwrap());
+
+        for outdated in 0..MAX_ENTRIES_PER_SUBJECT + 2 {
+            history
+                .record(&crate_path, summary(outdated, 0))
+                .await
+                .unwrap();
+        }
+
+        let entries = history.load(&crate_path, 1000).await.unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES_PER_SUBJECT);
+        assert_eq!(entries[0].outdated, MAX_ENTRIES_PER_SUBJECT + 1);
+        assert_eq!(entries.last().unwrap().outdated, 2);
+
+        let dependencies = history
+            .with_conn(|conn| {
+                let count = conn.query_row(
+                    "SELECT COUNT(*) FROM analyzed_dependencies",
+                    params![],
+                    |row| row.get::<_, i64>(0),
+                )?;
+                Ok(count as usize)
+            })
+            .await
+            .unwrap();
+        assert_eq!(dependencies, MAX_ENTRIES_PER_SUBJECT);
+    }
+}
@@ -1,13 +1,76 @@
+use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Error, Result};
-use crates_index::Index;
+use crates_index::{BareIndex, Crate, Index};
 use slog::{error, info, Logger};
 use tokio::task::spawn_blocking;
 use tokio::time::{self, Interval};
 
+/// A registry index that crates can be looked up in
+#[derive(Clone)]
+pub enum CrateIndex {
+    /// Checkout of the crates.io-index
+    CratesIo(Index),
+    /// Bare clone of an alternate registry's index
+    Registry(Arc<BareIndex>),
+}
+
+impl CrateIndex {
+    /// Runs `f` with a function that looks up crates in the index, opening the index only once.
+    /// This reads from disk, so it should not be called on the runtime.
+    pub fn with_lookup<R>(
+        &self,
+        f: impl FnOnce(&dyn Fn(&str) -> Option<Crate>) -> Result<R>,
+    ) -> Result<R> {
+        match self {
+            CrateIndex::CratesIo(index) => f(&|name| index.crate_(name)),
+            CrateIndex::Registry(index) => {
+                let repo = index.open_or_clone()?;
+                f(&|name| repo.crate_(name))
+            }
+        }
+    }
+
+    fn retrieve(&self, logger: &Logger, name: &str) -> Result<()> {
+        match self {
+            CrateIndex::CratesIo(index) => {
+                if !index.exists() {
+                    info!(logger, "Cloning {}", name);
+                    index.retrieve()?;
+                }
+            }
+            // opening a bare index clones it if necessary
+            CrateIndex::Registry(index) => {
+                index.open_or_clone()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn retrieve_or_update(&self) -> Result<()> {
+        match self {
+            CrateIndex::CratesIo(index) => index.retrieve_or_update()?,
+            CrateIndex::Registry(index) => index.open_or_clone()?.retrieve()?,
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CrateIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrateIndex::CratesIo(index) => f.debug_tuple("CratesIo").field(index).finish(),
+            CrateIndex::Registry(_) => f.write_str("Registry"),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct ManagedIndex {
-    index: Index,
+    index: CrateIndex,
+    name: String,
     update_interval: Interval,
     logger: Logger,
 }
@@ -15,28 +78,44 @@ pub struct ManagedIndex {
 impl ManagedIndex {
     pub fn new(update_interval: Duration, logger: Logger) -> Self {
         // the index path is configurable through the `CARGO_HOME` env variable
-        let index = Index::new_cargo_default();
+        let index = CrateIndex::CratesIo(Index::new_cargo_default());
         let update_interval = time::interval(update_interval);
         Self {
             index,
+            name: "crates.io-index".to_string(),
             update_interval,
             logger,
         }
     }
 
-    pub fn index(&self) -> Index {
+    /// Manages the index of an alternate registry, stored where cargo would keep it
+    pub fn for_registry(
+        name: &str,
+        url: &str,
+        update_interval: Duration,
+        logger: Logger,
+    ) -> Result<Self> {
+        let index = CrateIndex::Registry(Arc::new(BareIndex::from_url(url)?));
+        let update_interval = time::interval(update_interval);
+        Ok(Self {
+            index,
+            name: format!("{} registry index", name),
+            update_interval,
+            logger,
+        })
+    }
+
+    pub fn index(&self) -> CrateIndex {
         self.index.clone()
     }
 
     pub async fn initial_clone(&mut self) -> Result<()> {
         let index = self.index();
         let logger = self.logger.clone();
+        let name = self.name.clone();
 
         spawn_blocking(move || {
-            if !index.exists() {
-                info!(logger, "Cloning crates.io-index");
-                index.retrieve()?;
-            }
+            index.retrieve(&logger, &name)?;
             Ok::<_, Error>(())
         })
         .await??;
@@ -48,7 +127,7 @@ impl ManagedIndex {
             if let Err(e) = self.refresh().await {
                 error!(
                     self.logger,
-                    "failed refreshing the crates.io-index, the operation will be retried: {}", e
+                    "failed refreshing the {}, the operation will be retried: {}", self.name, e
                 );
             }
             self.update_interval.tick().await;
@@ -1,2 +1,4 @@
+pub mod advisory_db;
 pub mod cache;
+pub mod history;
 pub mod index;